@@ -0,0 +1,81 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::evolution::EvolutionState;
+use crate::optimizer::CollageOptimizer;
+use crate::packing::{LayoutConfig, LayoutMode};
+
+/// A GA run saved at the start of a generation, enough to continue it with
+/// [`CollageOptimizer::from_checkpoint`] as if it had never been interrupted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint<G> {
+    /// [`image_set_hash`](crate::image_handling::image_set_hash) of the images of the run.
+    pub input_hash: u64,
+    /// Number of pages of a partition run, `None` for a single collage.
+    pub pages: Option<usize>,
+    /// Settings of the run, including the seed it used.
+    pub optimizer: CollageOptimizer,
+    pub state: EvolutionState<G>,
+}
+
+/// What [`checkpoint_info`] reports about a saved run.
+#[derive(Clone, Copy, Debug)]
+pub struct CheckpointInfo {
+    /// Number of pages of a partition run, `None` for a single collage.
+    pub pages: Option<usize>,
+    pub mode: LayoutMode,
+    /// Whether the run is a [`CollageOptimizer::run_pareto`].
+    pub pareto: bool,
+}
+
+/// The parts of a checkpoint that tell how to resume it.
+#[derive(Deserialize)]
+struct CheckpointHeader {
+    pages: Option<usize>,
+    optimizer: OptimizerHeader,
+}
+
+#[derive(Deserialize)]
+struct OptimizerHeader {
+    layout: LayoutConfig,
+    pareto: bool,
+}
+
+/// Writes `checkpoint` as JSON, replacing `path`. The file is written under a
+/// temporary name first, so an interruption never leaves a truncated checkpoint.
+pub fn write_checkpoint<G: Serialize>(checkpoint: &Checkpoint<G>, path: &Path) -> Result<(), String> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path: PathBuf = path.with_file_name(tmp_name);
+
+    let file = File::create(&tmp_path).map_err(|e| format!("Error creating checkpoint {}: {}", tmp_path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, checkpoint)
+        .map_err(|e| e.to_string())
+        .and_then(|_| writer.flush().map_err(|e| e.to_string()))
+        .map_err(|e| format!("Error writing checkpoint {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Error writing checkpoint {}: {}", path.display(), e))
+}
+
+/// Reads a checkpoint written by [`write_checkpoint`].
+pub fn read_checkpoint<G: DeserializeOwned>(path: &Path) -> Result<Checkpoint<G>, String> {
+    let file = File::open(path).map_err(|e| format!("Error opening checkpoint {}: {}", path.display(), e))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Error reading checkpoint {}: {}", path.display(), e))
+}
+
+/// The kind of run saved at `path`, which tells the genome type [`read_checkpoint`] needs:
+/// a `PartitionIndividual` with pages, else a `SlicingTree` in the slicing mode or an `Individual`.
+pub fn checkpoint_info(path: &Path) -> Result<CheckpointInfo, String> {
+    let file = File::open(path).map_err(|e| format!("Error opening checkpoint {}: {}", path.display(), e))?;
+    let header: CheckpointHeader = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Error reading checkpoint {}: {}", path.display(), e))?;
+    Ok(CheckpointInfo {
+        pages: header.pages,
+        mode: header.optimizer.layout.mode,
+        pareto: header.optimizer.pareto,
+    })
+}
@@ -1,10 +1,78 @@
-use clap::{App, Arg};
+use std::time::Duration;
 
-pub fn parse_args() -> (String, Option<String>, Option<u32>, usize, usize, usize, usize, f64, f64) {
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use image_grid_optimizer::packing::{BottomEdge, CropStrategy, LayoutMode, PackerKind, DEFAULT_ASPECT_RATIO, DEFAULT_PADDING};
+use image_grid_optimizer::evolution::{StopCriteria, DEFAULT_TOURNAMENT_SIZE};
+use image_grid_optimizer::{
+    CrossoverKind, DedupeMode, Elitism, FitnessTerm, LayoutConfig, OutputFormat, OutputOptions, RenderOptions, Selection,
+};
+
+/// Command line options of the optimizer.
+pub struct Args {
+    pub dir: String,
+    pub filter: Option<String>,
+    pub standard_width: Option<u32>,
+    pub population_size: usize,
+    pub generations: usize,
+    pub min_images: usize,
+    pub max_images: usize,
+    pub scale_min: f64,
+    pub scale_max: f64,
+    pub mutation_rate: f64,
+    pub crossover_rate: f64,
+    pub crossover: CrossoverKind,
+    pub selection: Selection,
+    pub elitism: Elitism,
+    pub stop: StopCriteria,
+    /// JSON file with the weights of the fitness terms.
+    pub fitness_config: Option<String>,
+    /// Weights given with `--weight`, applied over those of the config file.
+    pub weights: Vec<(FitnessTerm, f64)>,
+    /// RGB colour the palette of the collage is pulled toward.
+    pub target_colour: Option<[u8; 3]>,
+    pub seed: Option<u64>,
+    pub layout: LayoutConfig,
+    /// Collage path, or the directory of the page images in partition mode.
+    pub output: Option<String>,
+    pub output_options: OutputOptions,
+    pub layout_json: Option<String>,
+    /// Distribute all images over this many collages.
+    pub pages: Option<usize>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Largest Hamming distance of the perceptual hashes of near-duplicate images.
+    pub dedupe: Option<u32>,
+    pub dedupe_mode: DedupeMode,
+    /// File the state of the run is saved to periodically.
+    pub checkpoint: Option<String>,
+    pub checkpoint_every: usize,
+    /// Checkpoint to continue instead of starting a new run.
+    pub resume: Option<String>,
+    /// Optimize the objectives separately and write the Pareto trade-offs.
+    pub pareto: bool,
+}
+
+/// Options of the `render` subcommand.
+pub struct RenderArgs {
+    pub layout: String,
+    pub render_options: RenderOptions,
+    pub output: String,
+    pub output_options: OutputOptions,
+}
+
+pub enum Command {
+    /// Run the GA over a directory of images.
+    Optimize(Box<Args>),
+    /// Re-render a saved layout.
+    Render(RenderArgs),
+}
+
+pub fn parse_args() -> Command {
     let matches = App::new("ImageGridOptimizer GA")
         .version("1.0")
         .author("Senior Developer")
         .about("Optimizes the arrangement of images using a Genetic Algorithm.")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("DIRECTORY")
                 .help("Directory containing the images.")
@@ -55,6 +123,20 @@ pub fn parse_args() -> (String, Option<String>, Option<u32>, usize, usize, usize
                 .help("Maximum number of images per collage.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("scale_min")
+                .long("scale-min")
+                .value_name("FACTOR")
+                .help("Smallest factor the GA may resize an image by (default: 1.0).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("scale_max")
+                .long("scale-max")
+                .value_name("FACTOR")
+                .help("Largest factor the GA may resize an image by (default: 1.0).")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("mutation_rate")
                 .long("mutation-rate")
@@ -69,8 +151,309 @@ pub fn parse_args() -> (String, Option<String>, Option<u32>, usize, usize, usize
                 .help("Crossover rate for the genetic algorithm.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("crossover")
+                .long("crossover")
+                .value_name("OPERATOR")
+                .help("Crossover operator: ox (default), pmx or cx keep the packing order, cut only recombines the selection.")
+                .possible_values(&["ox", "pmx", "cx", "cut"])
+                .case_insensitive(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("selection")
+                .long("selection")
+                .value_name("STRATEGY")
+                .help("How parents are picked: truncation (default, uniformly from the fittest half), tournament, roulette or rank.")
+                .possible_values(&["truncation", "tournament", "roulette", "rank"])
+                .case_insensitive(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tournament_size")
+                .long("tournament-size")
+                .value_name("SIZE")
+                .help("Number of competitors per tournament with --selection tournament (default: 3).")
+                .requires("selection")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("elitism")
+                .long("elitism")
+                .value_name("N or N%")
+                .help("Number or percentage of the fittest individuals carried over unchanged (default: 50%).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_stale")
+                .long("max-stale")
+                .value_name("GENERATIONS")
+                .help("Stop after this many generations without a new best fitness.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("target_fitness")
+                .long("target-fitness")
+                .value_name("FITNESS")
+                .help("Stop as soon as the best fitness reaches this value.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("time_limit")
+                .long("time-limit")
+                .value_name("DURATION")
+                .help("Stop after this much time, e.g. 90s, 5m or 1.5h (plain numbers are seconds).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("min_diversity")
+                .long("min-diversity")
+                .value_name("FRACTION")
+                .help("Stop when the population differs from its best individual in less than this share of the genome on average (0 to 1).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("weight")
+                .long("weight")
+                .value_name("TERM=WEIGHT")
+                .help("Weight of a fitness term: image_count, whitespace, aspect_deviation, cropping, row_variation, size_uniformity, colour_harmony, orientation_balance, required_coverage, brightness_balance, adjacent_colours, palette_target or near_duplicates. Can be repeated.")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fitness_config")
+                .long("fitness-config")
+                .value_name("FILE")
+                .help("JSON file mapping fitness terms to weights, e.g. {\"whitespace\": 2}; --weight overrides it.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("target_colour")
+                .long("target-colour")
+                .value_name("COLOR")
+                .help("Pull the mean colour of the collage toward this colour (#RRGGBB or R,G,B), weighted by the palette_target term.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed for the random number generator; the same seed and images reproduce the same collage.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("aspect")
+                .long("aspect")
+                .value_name("RATIO")
+                .help("Target aspect ratio of the collage as W:H (e.g. 16:9, 9:19.5) or a number (default: 1).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("canvas")
+                .long("canvas")
+                .value_name("WxH")
+                .help("Exact output size in pixels, e.g. 3840x2160. Its ratio is used as the target aspect ratio.")
+                .conflicts_with("aspect")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fill")
+                .long("fill")
+                .help("Scale the chosen images so they fill the --canvas frame as completely as possible.")
+                .requires("canvas"),
+        )
+        .arg(
+            Arg::with_name("justified")
+                .long("justified")
+                .help("Arrange the images in rows of equal width (web gallery style) instead of packing them.")
+                .conflicts_with_all(&["fill", "allow_rotation", "packer"]),
+        )
+        .arg(
+            Arg::with_name("grid")
+                .long("grid")
+                .value_name("RxC")
+                .help("Arrange exactly R*C images in a grid of identical cells, cropping them to the cell shape.")
+                .conflicts_with_all(&["justified", "fill", "allow_rotation", "packer", "pages"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("grid_crop")
+                .long("grid-crop")
+                .value_name("STRATEGY")
+                .help("Part of each image kept in its grid cell: center or entropy (the most detailed part).")
+                .possible_values(&["center", "entropy"])
+                .case_insensitive(true)
+                .requires("grid")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("masonry")
+                .long("masonry")
+                .value_name("COLUMNS")
+                .help("Stack the images in this many columns of equal width (Pinterest style).")
+                .conflicts_with_all(&["justified", "grid", "fill", "allow_rotation", "packer"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("masonry_bottom")
+                .long("masonry-bottom")
+                .value_name("EDGE")
+                .help("How the columns get a clean bottom edge: crop them to the shortest column (default) or pad them to the tallest.")
+                .possible_values(&["crop", "pad"])
+                .case_insensitive(true)
+                .requires("masonry")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("slicing")
+                .long("slicing")
+                .help("Cut the collage recursively into parts holding one image each (magazine style), leaving no free area.")
+                .conflicts_with_all(&["justified", "grid", "masonry", "fill", "allow_rotation", "packer", "pages"]),
+        )
+        .arg(
+            Arg::with_name("allow_rotation")
+                .long("allow-rotation")
+                .help("Let the packer turn images by 90° when that packs them tighter."),
+        )
+        .arg(
+            Arg::with_name("packer")
+                .long("packer")
+                .value_name("PACKER")
+                .help("Packing heuristic (default: rect-packer).")
+                .possible_values(&["rect-packer", "maxrects-bssf", "maxrects-baf", "skyline", "guillotine", "shelf"])
+                .case_insensitive(true)
+                .takes_value(true),
+        )
+        .args(&spacing_args())
+        .args(&output_args())
+        .arg(
+            Arg::with_name("layout_json")
+                .long("layout-json")
+                .value_name("PATH")
+                .help("Also write the optimized layout (placements, sizes, fitness breakdown) as JSON. A directory in --pages mode.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pages")
+                .long("pages")
+                .value_name("PAGES")
+                .help("Place every image exactly once across this many collages, written as page_001.png, page_002.png, ... into the --output directory.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pareto")
+                .long("pareto")
+                .help("Optimize the image count, free area and aspect ratio as separate objectives (NSGA-II) and write the trade-offs most_images, least_whitespace, closest_aspect and best_fitness into the --output directory.")
+                .conflicts_with_all(&["pages", "selection", "elitism"]),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .value_name("GLOB")
+                .help("Images whose file name or path matches this glob appear in every collage. Can be repeated.")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .help("Images whose file name or path matches this glob are never used. Can be repeated.")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dedupe")
+                .long("dedupe")
+                .value_name("THRESHOLD")
+                .help("Treat images whose perceptual hashes differ in at most this many of 64 bits as near-duplicates (e.g. 10).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dedupe_mode")
+                .long("dedupe-mode")
+                .value_name("MODE")
+                .help("What to do with near-duplicates: drop all but one before optimising, or penalise collages that show them together.")
+                .possible_values(&["drop", "penalise", "penalize"])
+                .requires("dedupe")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .value_name("FILE")
+                .help("Save the state of the run to this file periodically so it can be continued with --resume.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("checkpoint_every")
+                .long("checkpoint-every")
+                .value_name("GENERATIONS")
+                .help("Generations between two checkpoints (default: 50).")
+                .requires("checkpoint")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .value_name("FILE")
+                .help("Continue the run saved in this checkpoint. The GA and layout options are taken from the checkpoint; the directory, --filter and --width must be those of the original run.")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Re-renders a collage from a layout file written with --layout-json, without rerunning the GA.")
+                .arg(
+                    Arg::with_name("LAYOUT")
+                        .help("Layout JSON file produced by the optimizer.")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("scale")
+                        .long("scale")
+                        .value_name("SCALE")
+                        .help("Factor applied to the canvas and every placement (default: 1.0).")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("canvas")
+                        .long("canvas")
+                        .value_name("WxH")
+                        .help("Exact output size in pixels; the layout is scaled to fit and centered.")
+                        .conflicts_with("scale")
+                        .takes_value(true),
+                )
+                .args(&spacing_args())
+                .args(&output_args()),
+        )
         .get_matches();
 
+    if let Some(render_matches) = matches.subcommand_matches("render") {
+        let layout = render_matches.value_of("LAYOUT").unwrap().to_string();
+        let scale = render_matches.value_of("scale").unwrap_or("1.0").parse::<f64>().expect("Invalid scale");
+        let canvas = render_matches
+            .value_of("canvas")
+            .map(|c| parse_size(c).expect("Invalid canvas size"));
+        let (output, output_options) = parse_output_args(render_matches);
+        let output = output.unwrap_or_else(|| "output.jpg".to_string());
+        return Command::Render(RenderArgs {
+            layout,
+            render_options: RenderOptions {
+                scale,
+                canvas,
+                padding: parse_padding(render_matches),
+                margin: parse_margin(render_matches),
+                background: parse_background(render_matches),
+            },
+            output,
+            output_options,
+        });
+    }
+
     let dir = matches.value_of("DIRECTORY").unwrap().to_string();
     let filter = matches.value_of("filter").map(|s| s.to_string());
     let standard_width = matches
@@ -78,12 +461,343 @@ pub fn parse_args() -> (String, Option<String>, Option<u32>, usize, usize, usize
         .map(|w| w.parse::<u32>().expect("Invalid width"));
 
     // Default large values to handle large number of trials
-    let population_size = matches.value_of("pop_size").unwrap_or("1000").parse::<usize>().expect("Invalid population size");
-    let generations = matches.value_of("gens").unwrap_or("3000").parse::<usize>().expect("Invalid number of generations");
+    let population_size = matches.value_of("population_size").unwrap_or("1000").parse::<usize>().expect("Invalid population size");
+    let generations = matches.value_of("generations").unwrap_or("3000").parse::<usize>().expect("Invalid number of generations");
     let min_images = matches.value_of("min_images").unwrap_or("6").parse::<usize>().expect("Invalid min_images");
     let max_images = matches.value_of("max_images").unwrap_or("60").parse::<usize>().expect("Invalid max_images");
+    let scale_min = matches.value_of("scale_min").unwrap_or("1.0").parse::<f64>().expect("Invalid scale-min");
+    let scale_max = matches.value_of("scale_max").unwrap_or("1.0").parse::<f64>().expect("Invalid scale-max");
     let mutation_rate = matches.value_of("mutation_rate").unwrap_or("0.1").parse::<f64>().expect("Invalid mutation rate");
     let crossover_rate = matches.value_of("crossover_rate").unwrap_or("0.7").parse::<f64>().expect("Invalid crossover rate");
+    let crossover = matches
+        .value_of("crossover")
+        .map(|c| c.parse::<CrossoverKind>().expect("Invalid crossover"))
+        .unwrap_or_default();
+    let tournament_size = matches
+        .value_of("tournament_size")
+        .map(|t| t.parse::<usize>().expect("Invalid tournament size"))
+        .unwrap_or(DEFAULT_TOURNAMENT_SIZE);
+    let selection = match matches.value_of("selection").map(|s| s.parse::<Selection>().expect("Invalid selection")) {
+        Some(Selection::Tournament(_)) => Selection::Tournament(tournament_size),
+        selection => selection.unwrap_or_default(),
+    };
+    let elitism = matches
+        .value_of("elitism")
+        .map(|e| e.parse::<Elitism>().expect("Invalid elitism"))
+        .unwrap_or_default();
+    let stop = StopCriteria {
+        max_stale_generations: matches
+            .value_of("max_stale")
+            .map(|g| g.parse::<usize>().expect("Invalid number of stale generations")),
+        target_fitness: matches
+            .value_of("target_fitness")
+            .map(|f| f.parse::<f64>().expect("Invalid target fitness")),
+        time_limit: matches
+            .value_of("time_limit")
+            .map(|t| parse_duration(t).expect("Invalid time limit")),
+        min_diversity: matches
+            .value_of("min_diversity")
+            .map(|d| d.parse::<f64>().expect("Invalid minimum diversity")),
+    };
+    let fitness_config = matches.value_of("fitness_config").map(|s| s.to_string());
+    let weights = matches
+        .values_of("weight")
+        .map(|values| values.map(|w| parse_weight(w).expect("Invalid weight")).collect())
+        .unwrap_or_default();
+    let target_colour = matches.value_of("target_colour").map(|c| {
+        let [r, g, b, _] = parse_color(c).expect("Invalid target colour");
+        [r, g, b]
+    });
+    let seed = matches.value_of("seed").map(|s| s.parse::<u64>().expect("Invalid seed"));
+    let aspect_ratio = matches
+        .value_of("aspect")
+        .map(|a| parse_aspect_ratio(a).expect("Invalid aspect ratio"))
+        .unwrap_or(DEFAULT_ASPECT_RATIO);
+    let canvas = matches
+        .value_of("canvas")
+        .map(|c| parse_size(c).expect("Invalid canvas size"));
+    let layout = LayoutConfig {
+        aspect_ratio,
+        canvas,
+        fill_canvas: matches.is_present("fill"),
+        padding: parse_padding(&matches).unwrap_or(DEFAULT_PADDING),
+        margin: parse_margin(&matches).unwrap_or(0),
+        background: parse_background(&matches).unwrap_or([255, 255, 255, 255]),
+        allow_rotation: matches.is_present("allow_rotation"),
+        packer: matches
+            .value_of("packer")
+            .map(|p| p.parse::<PackerKind>().expect("Invalid packer"))
+            .unwrap_or_default(),
+        mode: parse_layout_mode(&matches),
+    };
+    let (output, output_options) = parse_output_args(&matches);
+    let layout_json = matches.value_of("layout_json").map(|s| s.to_string());
+    let pages = matches.value_of("pages").map(|p| p.parse::<usize>().expect("Invalid number of pages"));
+    let globs = |name| {
+        matches
+            .values_of(name)
+            .map(|values| values.map(|v: &str| v.to_string()).collect())
+            .unwrap_or_default()
+    };
+    let include = globs("include");
+    let exclude = globs("exclude");
+    let dedupe = matches
+        .value_of("dedupe")
+        .map(|t| parse_dedupe_threshold(t).expect("Invalid dedupe threshold"));
+    let dedupe_mode = matches
+        .value_of("dedupe_mode")
+        .map(|m| m.parse::<DedupeMode>().expect("Invalid dedupe mode"))
+        .unwrap_or_default();
+    let checkpoint = matches.value_of("checkpoint").map(|s| s.to_string());
+    let checkpoint_every = matches
+        .value_of("checkpoint_every")
+        .unwrap_or("50")
+        .parse::<usize>()
+        .expect("Invalid checkpoint interval");
+    let resume = matches.value_of("resume").map(|s| s.to_string());
+    let pareto = matches.is_present("pareto");
+
+    Command::Optimize(Box::new(Args {
+        dir,
+        filter,
+        standard_width,
+        population_size,
+        generations,
+        min_images,
+        max_images,
+        scale_min,
+        scale_max,
+        mutation_rate,
+        crossover_rate,
+        crossover,
+        selection,
+        elitism,
+        stop,
+        fitness_config,
+        weights,
+        target_colour,
+        seed,
+        layout,
+        output,
+        output_options,
+        layout_json,
+        pages,
+        include,
+        exclude,
+        dedupe,
+        dedupe_mode,
+        checkpoint,
+        checkpoint_every,
+        resume,
+        pareto,
+    }))
+}
+
+/// Options controlling the spacing and background of the collage.
+fn spacing_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("padding")
+            .long("padding")
+            .value_name("PIXELS")
+            .help("Spacing between neighbouring images (default: 5).")
+            .takes_value(true),
+        Arg::with_name("margin")
+            .long("margin")
+            .value_name("PIXELS")
+            .help("Empty border around the whole collage (default: 0).")
+            .takes_value(true),
+        Arg::with_name("background")
+            .long("background")
+            .value_name("COLOR")
+            .help("Background colour as #RRGGBB, #RRGGBBAA, R,G,B[,A] or 'transparent' (default: white).")
+            .takes_value(true),
+    ]
+}
+
+fn parse_padding(matches: &ArgMatches) -> Option<u32> {
+    matches.value_of("padding").map(|p| p.parse::<u32>().expect("Invalid padding"))
+}
+
+fn parse_margin(matches: &ArgMatches) -> Option<u32> {
+    matches.value_of("margin").map(|m| m.parse::<u32>().expect("Invalid margin"))
+}
+
+fn parse_background(matches: &ArgMatches) -> Option<[u8; 4]> {
+    matches
+        .value_of("background")
+        .map(|c| parse_color(c).expect("Invalid background colour"))
+}
+
+/// Options controlling where and how the collage image is written.
+fn output_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("PATH")
+            .help("Path of the collage image; the format is inferred from the extension (default: output.jpg).")
+            .takes_value(true),
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Output format overriding the file extension.")
+            .possible_values(&["png", "jpg", "jpeg", "webp", "tif", "tiff", "bmp"])
+            .case_insensitive(true)
+            .takes_value(true),
+        Arg::with_name("quality")
+            .long("quality")
+            .value_name("QUALITY")
+            .help("JPEG encoding quality from 1 to 100 (default: 90).")
+            .takes_value(true),
+        Arg::with_name("force")
+            .long("force")
+            .help("Overwrite existing output files instead of refusing to write them."),
+    ]
+}
+
+fn parse_output_args(matches: &ArgMatches) -> (Option<String>, OutputOptions) {
+    let output = matches.value_of("output").map(|s| s.to_string());
+    let format = matches
+        .value_of("format")
+        .map(|f| f.parse::<OutputFormat>().expect("Invalid format"));
+    let jpeg_quality = matches.value_of("quality").unwrap_or("90").parse::<u8>().expect("Invalid quality");
+    if !(1..=100).contains(&jpeg_quality) {
+        panic!("Invalid quality: must be between 1 and 100");
+    }
+    let output_options = OutputOptions {
+        format,
+        jpeg_quality,
+        force: matches.is_present("force"),
+    };
+    (output, output_options)
+}
+
+/// Layout mode from `--grid`/`--masonry`/`--justified`/`--slicing`, packed otherwise.
+fn parse_layout_mode(matches: &ArgMatches) -> LayoutMode {
+    if let Some(grid) = matches.value_of("grid") {
+        let (rows, columns) = parse_size(grid).expect("Invalid grid size");
+        let crop = matches
+            .value_of("grid_crop")
+            .map(|c| c.parse::<CropStrategy>().expect("Invalid grid crop"))
+            .unwrap_or_default();
+        return LayoutMode::Grid { rows, columns, crop };
+    }
+    if let Some(columns) = matches.value_of("masonry") {
+        let columns = columns.parse::<u32>().expect("Invalid number of columns");
+        let bottom = matches
+            .value_of("masonry_bottom")
+            .map(|b| b.parse::<BottomEdge>().expect("Invalid bottom edge"))
+            .unwrap_or_default();
+        return LayoutMode::Masonry { columns, bottom };
+    }
+    if matches.is_present("justified") {
+        return LayoutMode::JustifiedRows;
+    }
+    if matches.is_present("slicing") {
+        return LayoutMode::Slicing;
+    }
+    LayoutMode::Packed
+}
+
+/// Parses an aspect ratio given as `W:H` (e.g. `16:9`, `9:19.5`) or as a plain number.
+fn parse_aspect_ratio(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => {
+            let w = w.trim().parse::<f64>().map_err(|e| format!("{}: {}", s, e))?;
+            let h = h.trim().parse::<f64>().map_err(|e| format!("{}: {}", s, e))?;
+            w / h
+        }
+        None => s.trim().parse::<f64>().map_err(|e| format!("{}: {}", s, e))?,
+    };
+    if ratio > 0.0 && ratio.is_finite() {
+        Ok(ratio)
+    } else {
+        Err(format!("{}: must be positive", s))
+    }
+}
+
+/// Parses the weight of a fitness term given as `TERM=WEIGHT`, e.g. `whitespace=2`.
+fn parse_weight(s: &str) -> Result<(FitnessTerm, f64), String> {
+    let (term, weight) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{}: expected TERM=WEIGHT, e.g. whitespace=2", s))?;
+    let weight = weight.trim().parse::<f64>().map_err(|e| format!("{}: {}", s, e))?;
+    Ok((term.parse::<FitnessTerm>()?, weight))
+}
+
+/// Parses a Hamming distance between two 64-bit hashes, from 0 to 64.
+fn parse_dedupe_threshold(s: &str) -> Result<u32, String> {
+    let threshold = s.trim().parse::<u32>().map_err(|e| format!("{}: {}", s, e))?;
+    if threshold > 64 {
+        return Err(format!("{}: must be between 0 and 64", s));
+    }
+    Ok(threshold)
+}
+
+/// Parses a duration given in seconds, minutes or hours, e.g. `90s`, `5m` or `1.5h`.
+/// A plain number is taken as seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (value, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value = value.trim().parse::<f64>().map_err(|e| format!("{}: {}", s, e))?;
+    let seconds = match unit {
+        "s" => value,
+        "m" | "min" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(format!("{}: unknown unit '{}', expected s, m or h", s, unit)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{}: {}", s, e))
+}
+
+/// Parses a size given as `WxH`, e.g. a `3840x2160` canvas or a `3x4` grid.
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("{}: expected a size such as 3840x2160", s))?;
+    let w = w.trim().parse::<u32>().map_err(|e| format!("{}: {}", s, e))?;
+    let h = h.trim().parse::<u32>().map_err(|e| format!("{}: {}", s, e))?;
+    if w == 0 || h == 0 {
+        return Err(format!("{}: width and height must be positive", s));
+    }
+    Ok((w, h))
+}
+
+/// Parses a colour given as `#RGB`, `#RRGGBB`, `#RRGGBBAA` (the `#` is optional),
+/// as comma separated `R,G,B[,A]` components or as `transparent`.
+fn parse_color(s: &str) -> Result<[u8; 4], String> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("transparent") {
+        return Ok([0, 0, 0, 0]);
+    }
+    if s.contains(',') {
+        let components = s
+            .split(',')
+            .map(|c| c.trim().parse::<u8>().map_err(|e| format!("{}: {}", s, e)))
+            .collect::<Result<Vec<u8>, String>>()?;
+        return match components[..] {
+            [r, g, b] => Ok([r, g, b, 255]),
+            [r, g, b, a] => Ok([r, g, b, a]),
+            _ => Err(format!("{}: expected 3 or 4 components", s)),
+        };
+    }
 
-    (dir, filter, standard_width, population_size, generations, min_images, max_images, mutation_rate, crossover_rate)
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8).ok_or_else(|| format!("{}: invalid hex digit '{}'", s, c)))
+        .collect::<Result<Vec<u8>, String>>()?;
+    match digits.len() {
+        3 => Ok([digits[0] * 17, digits[1] * 17, digits[2] * 17, 255]),
+        6 | 8 => {
+            let mut color = [255; 4];
+            for (i, pair) in digits.chunks(2).enumerate() {
+                color[i] = pair[0] * 16 + pair[1];
+            }
+            Ok(color)
+        }
+        _ => Err(format!("{}: expected 3, 6 or 8 hex digits", s)),
+    }
 }
@@ -1,22 +1,71 @@
-use image::{DynamicImage, Rgba, GenericImage, GenericImageView};
-use rect_packer::Rect;
+use image::imageops::{resize, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage, GenericImage, GenericImageView};
 use std::collections::HashMap;
 
+use crate::packing::Placement;
+
+/// Draws the images at their placements on a `max_width` x `max_height` canvas,
+/// centring them as a whole. Fails if a placement does not fit on the canvas.
 pub fn create_collage(
     images: &HashMap<u32, DynamicImage>,
-    packed_locations: &[(u32, Rect)],
+    packed_locations: &[Placement],
     max_width: u32,
     max_height: u32,
-) -> DynamicImage {
-    println!("Creating collage...");
-    println!("Collage dimensions: Width = {}, Height = {}", max_width, max_height);
+    background: Rgba<u8>,
+) -> Result<DynamicImage, String> {
+    let (offset_x, offset_y) = centering_offset(packed_locations, max_width, max_height);
+
+    let mut collage = DynamicImage::ImageRgba8(RgbaImage::from_pixel(max_width, max_height, background));
 
+    // Place images with offset, rotating, cropping and resizing them to their placement
+    for Placement { id, rect, rotated, crop } in packed_locations {
+        if let Some(img) = images.get(id) {
+            let rotated_img;
+            let img = if *rotated {
+                rotated_img = img.rotate90();
+                &rotated_img
+            } else {
+                img
+            };
+            let cropped_img;
+            let img = match crop {
+                Some(crop) => {
+                    let (w, h) = img.dimensions();
+                    let x = ((crop.x * w as f64).round() as u32).min(w - 1);
+                    let y = ((crop.y * h as f64).round() as u32).min(h - 1);
+                    let crop_w = ((crop.width * w as f64).round() as u32).clamp(1, w - x);
+                    let crop_h = ((crop.height * h as f64).round() as u32).clamp(1, h - y);
+                    cropped_img = img.crop_imm(x, y, crop_w, crop_h);
+                    &cropped_img
+                }
+                None => img,
+            };
+            let target_x = (rect.x as i64 + offset_x) as u32;
+            let target_y = (rect.y as i64 + offset_y) as u32;
+            let (width, height) = (rect.width as u32, rect.height as u32);
+            let copied = if img.dimensions() == (width, height) {
+                collage.copy_from(img, target_x, target_y)
+            } else {
+                let resized = resize(img, width, height, FilterType::Lanczos3);
+                collage.copy_from(&resized, target_x, target_y)
+            };
+            copied.map_err(|e| format!("Error placing image {} at ({}, {}): {}", id, target_x, target_y, e))?;
+        }
+    }
+
+    Ok(collage)
+}
+
+/// Shift that centers the bounding box of the packed images on the canvas.
+///
+/// The final position of every image is its packed position plus this offset.
+pub fn centering_offset(packed_locations: &[Placement], max_width: u32, max_height: u32) -> (i64, i64) {
     let mut min_x = u32::MAX;
     let mut min_y = u32::MAX;
     let mut max_x = 0;
     let mut max_y = 0;
 
-    for (id, rect) in packed_locations {
+    for Placement { rect, .. } in packed_locations {
         let x_end = (rect.x + rect.width) as u32;
         let y_end = (rect.y + rect.height) as u32;
 
@@ -24,11 +73,9 @@ pub fn create_collage(
         if (rect.y as u32) < min_y { min_y = rect.y as u32; }
         if x_end > max_x { max_x = x_end; }
         if y_end > max_y { max_y = y_end; }
-
-        println!(
-            "Image ID: {}, Position: ({}, {}), Size: {}x{}",
-            id, rect.x, rect.y, rect.width, rect.height
-        );
+    }
+    if packed_locations.is_empty() {
+        return (0, 0);
     }
 
     let bounding_width = max_x - min_x;
@@ -37,23 +84,5 @@ pub fn create_collage(
     let offset_x = (max_width.saturating_sub(bounding_width)) / 2;
     let offset_y = (max_height.saturating_sub(bounding_height)) / 2;
 
-    let mut collage = DynamicImage::new_rgba8(max_width, max_height);
-
-    // Fill background with white
-    for y in 0..max_height {
-        for x in 0..max_width {
-            collage.put_pixel(x, y, Rgba([255, 255, 255, 255]));
-        }
-    }
-
-    // Place images with offset
-    for (id, rect) in packed_locations {
-        if let Some(img) = images.get(id) {
-            let target_x = offset_x + (rect.x as u32 - min_x);
-            let target_y = offset_y + (rect.y as u32 - min_y);
-            collage.copy_from(img, target_x, target_y).unwrap();
-        }
-    }
-
-    collage
+    (offset_x as i64 - min_x as i64, offset_y as i64 - min_y as i64)
 }
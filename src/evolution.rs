@@ -0,0 +1,391 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ga::{stream_rng, GaContext, GaRng, Genome};
+use crate::pareto::{rank_and_crowding, select_survivors};
+
+/// Parameters of the generational loop shared by all genome types.
+#[derive(Clone, Debug)]
+pub struct GaParams {
+    pub population_size: usize,
+    pub generations: usize,
+    pub mutation_rate: f64,
+    pub crossover_rate: f64,
+    pub selection: Selection,
+    pub elitism: Elitism,
+    pub stop: StopCriteria,
+    /// Optimize the [`Genome::objectives`] with NSGA-II instead of the fitness;
+    /// `selection` and `elitism` are then not used.
+    pub pareto: bool,
+}
+
+/// Conditions that end a run before `generations` is reached; all are off by default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StopCriteria {
+    /// Stop after this many generations without a new best fitness.
+    pub max_stale_generations: Option<usize>,
+    /// Stop as soon as the best fitness reaches this value.
+    pub target_fitness: Option<f64>,
+    /// Stop once this much time has passed since the start of the run. The
+    /// generation in progress is finished first.
+    pub time_limit: Option<Duration>,
+    /// Stop when the mean [`Genome::distance`] of the population to its fittest
+    /// member drops below this value.
+    pub min_diversity: Option<f64>,
+}
+
+/// Why a run ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    /// All generations were run.
+    Generations,
+    /// The best fitness did not improve for the given number of generations.
+    Stale(usize),
+    TargetFitness(f64),
+    TimeLimit(Duration),
+    /// The diversity fell to the given value.
+    DiversityCollapse(f64),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Generations => write!(f, "generation limit reached"),
+            StopReason::Stale(generations) => write!(f, "no improvement for {} generations", generations),
+            StopReason::TargetFitness(target) => write!(f, "target fitness {} reached", target),
+            StopReason::TimeLimit(limit) => write!(f, "time limit of {:.2?} reached", limit),
+            StopReason::DiversityCollapse(diversity) => write!(f, "population diversity collapsed to {:.4}", diversity),
+        }
+    }
+}
+
+/// Result of [`evolve`].
+pub struct Evolution<G> {
+    /// The final population, fittest first.
+    pub population: Vec<G>,
+    /// Number of the last generation.
+    pub generations: usize,
+    pub stop_reason: StopReason,
+}
+
+/// Mean distance of a population sorted by fitness to its fittest member.
+pub fn diversity<G: Genome>(population: &[G]) -> f64 {
+    if population.len() < 2 {
+        return 0.0;
+    }
+    let best = &population[0];
+    population[1..].iter().map(|g| best.distance(g)).sum::<f64>() / (population.len() - 1) as f64
+}
+
+/// The state of a run at the start of a generation; saving it lets the run be
+/// continued later with exactly the same result.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EvolutionState<G> {
+    /// The generation the run continues with.
+    pub generation: usize,
+    /// The RNG the generation seeds are drawn from.
+    pub rng: GaRng,
+    /// Best fitness of the previous generations.
+    pub best_fitness: Option<f64>,
+    /// Generations in a row that did not improve on `best_fitness`.
+    pub stale_generations: usize,
+    pub population: Vec<G>,
+}
+
+impl<G: Genome> EvolutionState<G> {
+    /// Checks the stop criteria for a population sorted by fitness, once per generation.
+    fn stop_reason(&mut self, criteria: &StopCriteria, start: Instant) -> Option<StopReason> {
+        let best = self.population[0].fitness();
+        if self.best_fitness.is_none_or(|best_fitness| best > best_fitness) {
+            self.best_fitness = Some(best);
+            self.stale_generations = 0;
+        } else {
+            self.stale_generations += 1;
+        }
+
+        if let Some(target) = criteria.target_fitness.filter(|&target| best >= target) {
+            return Some(StopReason::TargetFitness(target));
+        }
+        if let Some(max) = criteria.max_stale_generations.filter(|&max| self.stale_generations >= max) {
+            return Some(StopReason::Stale(max));
+        }
+        if let Some(limit) = criteria.time_limit.filter(|&limit| start.elapsed() >= limit) {
+            return Some(StopReason::TimeLimit(limit));
+        }
+        if let Some(min) = criteria.min_diversity {
+            let diversity = diversity(&self.population);
+            if diversity < min {
+                return Some(StopReason::DiversityCollapse(diversity));
+            }
+        }
+        None
+    }
+}
+
+/// How the parents of a child are picked from the current population.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Selection {
+    /// Uniformly from the fittest half.
+    #[default]
+    Truncation,
+    /// The fittest of the given number of uniformly drawn individuals.
+    Tournament(usize),
+    /// Proportional to the fitness.
+    Roulette,
+    /// Proportional to the rank, from `n` for the fittest down to 1.
+    Rank,
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    /// Parses the strategy name; tournaments get the size [`DEFAULT_TOURNAMENT_SIZE`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truncation" => Ok(Selection::Truncation),
+            "tournament" => Ok(Selection::Tournament(DEFAULT_TOURNAMENT_SIZE)),
+            "roulette" => Ok(Selection::Roulette),
+            "rank" => Ok(Selection::Rank),
+            _ => Err(format!("Unknown selection: {}", s)),
+        }
+    }
+}
+
+pub const DEFAULT_TOURNAMENT_SIZE: usize = 3;
+
+/// How many of the fittest individuals are carried over unchanged into the next generation.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Elitism {
+    Count(usize),
+    /// Percentage of the population, rounded down.
+    Percent(f64),
+}
+
+impl Default for Elitism {
+    fn default() -> Self {
+        Elitism::Percent(50.0)
+    }
+}
+
+impl Elitism {
+    /// Number of elites in a population of `population_size`.
+    pub fn count(&self, population_size: usize) -> usize {
+        match *self {
+            Elitism::Count(n) => n.min(population_size),
+            Elitism::Percent(p) => ((population_size as f64 * p / 100.0) as usize).min(population_size),
+        }
+    }
+}
+
+impl FromStr for Elitism {
+    type Err = String;
+
+    /// Parses `N` or `N%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid elitism: {}", s);
+        match s.trim().strip_suffix('%') {
+            Some(percent) => {
+                let percent = percent.trim().parse::<f64>().map_err(|_| invalid())?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(invalid());
+                }
+                Ok(Elitism::Percent(percent))
+            }
+            None => s.trim().parse::<usize>().map(Elitism::Count).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Parent selection prepared for one generation of a population sorted by fitness.
+enum Selector {
+    /// Uniformly among the first `n`.
+    Uniform(usize),
+    Tournament(usize),
+    Weighted(WeightedIndex<f64>),
+    /// Binary tournaments on the Pareto rank, then the crowding distance, of every individual.
+    Crowded(Vec<(usize, f64)>),
+}
+
+impl Selector {
+    fn new<G: Genome>(selection: Selection, population: &[G]) -> Self {
+        let n = population.len();
+        let weighted = |weights: Vec<f64>| match WeightedIndex::new(weights) {
+            Ok(index) => Selector::Weighted(index),
+            // All weights zero: nothing to prefer
+            Err(_) => Selector::Uniform(n),
+        };
+        match selection {
+            Selection::Truncation => Selector::Uniform((n / 2).max(1)),
+            Selection::Tournament(size) => Selector::Tournament(size.max(1)),
+            Selection::Roulette => weighted(population.iter().map(|g| g.fitness().max(0.0)).collect()),
+            Selection::Rank => weighted((0..n).map(|i| (n - i) as f64).collect()),
+        }
+    }
+
+    /// The parent selection of NSGA-II.
+    fn crowded<G: Genome>(population: &[G]) -> Self {
+        let objectives: Vec<Vec<f64>> = population.iter().map(|g| g.objectives()).collect();
+        Selector::Crowded(rank_and_crowding(&objectives))
+    }
+
+    /// Picks the next parent from the population the selector was prepared for.
+    fn select<'a, G>(&self, population: &'a [G], rng: &mut GaRng) -> &'a G {
+        match self {
+            Selector::Uniform(pool) => population[..*pool].choose(rng).unwrap(),
+            // The population is sorted, so the lowest index is the fittest
+            Selector::Tournament(size) => {
+                let winner = (0..*size).map(|_| rng.gen_range(0..population.len())).min().unwrap();
+                &population[winner]
+            }
+            Selector::Weighted(index) => &population[index.sample(rng)],
+            Selector::Crowded(ranking) => {
+                let a = rng.gen_range(0..population.len());
+                let b = rng.gen_range(0..population.len());
+                let ((rank_a, crowding_a), (rank_b, crowding_b)) = (ranking[a], ranking[b]);
+                if rank_b < rank_a || (rank_b == rank_a && crowding_b > crowding_a) {
+                    &population[b]
+                } else {
+                    &population[a]
+                }
+            }
+        }
+    }
+}
+
+/// Sorts a population by descending fitness.
+pub fn sort_by_fitness<G: Genome>(population: &mut [G]) {
+    population.sort_by(|a, b| b.fitness().partial_cmp(&a.fitness()).unwrap());
+}
+
+/// Evaluates every individual, e.g. after loading a population from a checkpoint.
+pub fn evaluate_population<G: Genome>(population: &mut [G], ctx: &GaContext) {
+    population.par_iter_mut().for_each(|indiv| indiv.evaluate(ctx));
+}
+
+/// Creates and evaluates the random initial population of a run.
+pub fn initial_state<G: Genome>(ctx: &GaContext, params: &GaParams, seed: u64) -> EvolutionState<G> {
+    let mut rng = GaRng::seed_from_u64(seed);
+
+    // Create and evaluate the initial population in parallel, one RNG stream per individual
+    let init_seed: u64 = rng.gen();
+    let population: Vec<G> = (0..params.population_size)
+        .into_par_iter()
+        .map(|i| {
+            let mut indiv_rng = stream_rng(init_seed, i as u64);
+            let mut indiv = G::random(ctx, &mut indiv_rng);
+            indiv.evaluate(ctx);
+            indiv
+        })
+        .collect();
+
+    EvolutionState {
+        generation: 1,
+        rng,
+        best_fitness: None,
+        stale_generations: 0,
+        population,
+    }
+}
+
+/// Runs the GA until the generation limit or one of the stop criteria is reached.
+///
+/// Every individual of a generation is created and evaluated on the rayon pool
+/// with its own RNG stream derived from `seed`, so the result only depends on
+/// the seed and the inputs, not on thread scheduling.
+pub fn evolve<G: Genome>(ctx: &GaContext, params: &GaParams, seed: u64, verbose: bool) -> Evolution<G> {
+    evolve_from(ctx, params, initial_state(ctx, params, seed), verbose, |_| {})
+}
+
+/// Runs the GA from `state`, e.g. one created by [`initial_state`] or restored
+/// from a checkpoint. `on_generation` is called with the state at the start of
+/// every generation, before the stop criteria are checked, and may save it.
+///
+/// The time limit counts from the call, not from the start of the original run.
+pub fn evolve_from<G: Genome>(
+    ctx: &GaContext,
+    params: &GaParams,
+    mut state: EvolutionState<G>,
+    verbose: bool,
+    mut on_generation: impl FnMut(&EvolutionState<G>),
+) -> Evolution<G> {
+    let start = Instant::now();
+
+    // GA main loop
+    let mut generations = params.generations;
+    let mut stop_reason = StopReason::Generations;
+    while state.generation <= params.generations {
+        let gen = state.generation;
+        sort_by_fitness(&mut state.population);
+        if verbose {
+            println!("Generation {}: Best fitness = {:.5}", gen, state.population[0].fitness());
+        }
+        on_generation(&state);
+        if let Some(reason) = state.stop_reason(&params.stop, start) {
+            generations = gen;
+            stop_reason = reason;
+            break;
+        }
+
+        let population = &state.population;
+        // In the Pareto mode all children compete with the whole population for a place
+        let (elite_count, selector) = if params.pareto {
+            (0, Selector::crowded(population))
+        } else {
+            (params.elitism.count(population.len()), Selector::new(params.selection, population))
+        };
+
+        // Create and evaluate new individuals in parallel; each child gets its own RNG
+        // stream derived from the generation seed so the result is schedule independent
+        let gen_seed: u64 = state.rng.gen();
+        let children: Vec<G> = (elite_count..params.population_size)
+            .into_par_iter()
+            .map(|i| {
+                let mut child_rng = stream_rng(gen_seed, i as u64);
+                let parent1 = selector.select(population, &mut child_rng);
+                let parent2 = selector.select(population, &mut child_rng);
+
+                let mut child = if child_rng.gen::<f64>() < params.crossover_rate {
+                    parent1.crossover(parent2, ctx, &mut child_rng)
+                } else {
+                    let mut c = parent1.clone();
+                    c.repair(ctx, &mut child_rng);
+                    c
+                };
+
+                if child_rng.gen::<f64>() < params.mutation_rate {
+                    child.mutate(ctx, &mut child_rng);
+                }
+
+                child.evaluate(ctx);
+                child
+            })
+            .collect();
+
+        if params.pareto {
+            let mut candidates = std::mem::take(&mut state.population);
+            candidates.extend(children);
+            state.population = select_survivors(candidates, params.population_size);
+        } else {
+            // Keep elites (already evaluated)
+            let mut new_population = population[..elite_count].to_vec();
+            new_population.extend(children);
+            state.population = new_population;
+        }
+        state.generation += 1;
+    }
+
+    let mut population = state.population;
+    sort_by_fitness(&mut population);
+    Evolution {
+        population,
+        generations,
+        stop_reason,
+    }
+}
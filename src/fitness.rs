@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::ga::FitnessBreakdown;
+
+/// A property of a collage that enters its fitness.
+///
+/// Rewards add to the numerator of the fitness, penalties to its denominator:
+/// `fitness = Σ weight·reward / (1 + Σ weight·penalty)`. A larger weight always
+/// means the term matters more.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FitnessTerm {
+    /// Number of images (reward).
+    ImageCount,
+    /// Free area of the collage in percent.
+    Whitespace,
+    /// Absolute difference between the collage and the target aspect ratio.
+    AspectDeviation,
+    /// Image content cut away by cropping, in percent.
+    Cropping,
+    /// Coefficient of variation of the row heights in the justified-rows mode.
+    RowVariation,
+    /// Coefficient of variation of the drawn image areas.
+    SizeUniformity,
+    /// Spread of the dominant hues of the images, from 0 (one hue) to 1.
+    ColourHarmony,
+    /// Surplus of landscape over portrait images or vice versa, from 0 to 1.
+    OrientationBalance,
+    /// Share of the drawn area taken by the included images (reward).
+    RequiredCoverage,
+    /// Difference in lightness between opposite halves of the collage, from 0 to 1.
+    BrightnessBalance,
+    /// Colour similarity of neighbouring images, from 0 to 1.
+    AdjacentColours,
+    /// Colour difference between the collage and the target colour; 0 without a target.
+    PaletteTarget,
+    /// Number of pairs of near-duplicate images; 0 unless they are penalised.
+    NearDuplicates,
+}
+
+impl FitnessTerm {
+    pub const ALL: [FitnessTerm; 13] = [
+        FitnessTerm::ImageCount,
+        FitnessTerm::Whitespace,
+        FitnessTerm::AspectDeviation,
+        FitnessTerm::Cropping,
+        FitnessTerm::RowVariation,
+        FitnessTerm::SizeUniformity,
+        FitnessTerm::ColourHarmony,
+        FitnessTerm::OrientationBalance,
+        FitnessTerm::RequiredCoverage,
+        FitnessTerm::BrightnessBalance,
+        FitnessTerm::AdjacentColours,
+        FitnessTerm::PaletteTarget,
+        FitnessTerm::NearDuplicates,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FitnessTerm::ImageCount => "image_count",
+            FitnessTerm::Whitespace => "whitespace",
+            FitnessTerm::AspectDeviation => "aspect_deviation",
+            FitnessTerm::Cropping => "cropping",
+            FitnessTerm::RowVariation => "row_variation",
+            FitnessTerm::SizeUniformity => "size_uniformity",
+            FitnessTerm::ColourHarmony => "colour_harmony",
+            FitnessTerm::OrientationBalance => "orientation_balance",
+            FitnessTerm::RequiredCoverage => "required_coverage",
+            FitnessTerm::BrightnessBalance => "brightness_balance",
+            FitnessTerm::AdjacentColours => "adjacent_colours",
+            FitnessTerm::PaletteTarget => "palette_target",
+            FitnessTerm::NearDuplicates => "near_duplicates",
+        }
+    }
+
+    /// Whether the term raises the fitness; all others lower it.
+    pub fn is_reward(self) -> bool {
+        matches!(self, FitnessTerm::ImageCount | FitnessTerm::RequiredCoverage)
+    }
+
+    /// The weight of the original fitness function; terms it did not have are off,
+    /// except for the palette target and near duplicates, which only count once a
+    /// target colour is set or near duplicates are penalised.
+    pub fn default_weight(self) -> f64 {
+        match self {
+            FitnessTerm::ImageCount | FitnessTerm::Whitespace | FitnessTerm::Cropping => 1.0,
+            FitnessTerm::AspectDeviation
+            | FitnessTerm::RowVariation
+            | FitnessTerm::PaletteTarget
+            | FitnessTerm::NearDuplicates => 10.0,
+            _ => 0.0,
+        }
+    }
+
+    /// The measured value of the term, before weighting.
+    pub fn value(self, breakdown: &FitnessBreakdown) -> f64 {
+        match self {
+            FitnessTerm::ImageCount => breakdown.image_count as f64,
+            FitnessTerm::Whitespace => breakdown.free_area_percentage,
+            FitnessTerm::AspectDeviation => breakdown.aspect_ratio_diff,
+            FitnessTerm::Cropping => breakdown.cropped_percentage,
+            FitnessTerm::RowVariation => breakdown.row_height_variation,
+            FitnessTerm::SizeUniformity => breakdown.size_variation,
+            FitnessTerm::ColourHarmony => breakdown.hue_spread,
+            FitnessTerm::OrientationBalance => breakdown.orientation_imbalance,
+            FitnessTerm::RequiredCoverage => breakdown.required_coverage,
+            FitnessTerm::BrightnessBalance => breakdown.brightness_imbalance,
+            FitnessTerm::AdjacentColours => breakdown.adjacent_colour_similarity,
+            FitnessTerm::PaletteTarget => breakdown.palette_distance,
+            FitnessTerm::NearDuplicates => breakdown.near_duplicates as f64,
+        }
+    }
+}
+
+impl FromStr for FitnessTerm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace('-', "_");
+        FitnessTerm::ALL
+            .into_iter()
+            .find(|term| term.name() == name)
+            .ok_or_else(|| format!("Unknown fitness term: {}", s))
+    }
+}
+
+/// The weight of every [`FitnessTerm`]; the default reproduces the original fitness function.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FitnessWeights(BTreeMap<FitnessTerm, f64>);
+
+impl Default for FitnessWeights {
+    fn default() -> Self {
+        FitnessWeights(FitnessTerm::ALL.into_iter().map(|term| (term, term.default_weight())).collect())
+    }
+}
+
+impl FitnessWeights {
+    pub fn get(&self, term: FitnessTerm) -> f64 {
+        self.0.get(&term).copied().unwrap_or_else(|| term.default_weight())
+    }
+
+    pub fn set(&mut self, term: FitnessTerm, weight: f64) {
+        self.0.insert(term, weight);
+    }
+
+    /// Checks that every weight is a non-negative number and that some reward counts.
+    pub fn validate(&self) -> Result<(), String> {
+        for term in FitnessTerm::ALL {
+            let weight = self.get(term);
+            if !(weight >= 0.0 && weight.is_finite()) {
+                return Err(format!("The weight of {} must be a non-negative number, got {}", term.name(), weight));
+            }
+        }
+        if FitnessTerm::ALL.iter().all(|&term| !term.is_reward() || self.get(term) == 0.0) {
+            return Err("At least one of image_count and required_coverage needs a positive weight.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Combines the terms of `breakdown` into the fitness value.
+    pub fn fitness(&self, breakdown: &FitnessBreakdown) -> f64 {
+        self.reward(breakdown) / self.penalty(breakdown)
+    }
+
+    /// The weighted sum of the reward terms, the numerator of the fitness.
+    pub fn reward(&self, breakdown: &FitnessBreakdown) -> f64 {
+        self.weighted_sum(breakdown, true, 0.0)
+    }
+
+    /// One plus the weighted sum of the penalty terms, the denominator of the fitness.
+    pub fn penalty(&self, breakdown: &FitnessBreakdown) -> f64 {
+        self.weighted_sum(breakdown, false, 1.0)
+    }
+
+    fn weighted_sum(&self, breakdown: &FitnessBreakdown, rewards: bool, start: f64) -> f64 {
+        FitnessTerm::ALL
+            .into_iter()
+            .filter(|term| term.is_reward() == rewards)
+            .fold(start, |sum, term| sum + self.get(term) * term.value(breakdown))
+    }
+}
+
+/// Reads weights from a JSON object mapping term names to weights, e.g.
+/// `{"whitespace": 2, "colour_harmony": 5}`. Terms it leaves out keep their default weight.
+pub fn read_fitness_weights(path: &Path) -> Result<FitnessWeights, String> {
+    let file = File::open(path).map_err(|e| format!("Error opening fitness config {}: {}", path.display(), e))?;
+    let overrides: BTreeMap<FitnessTerm, f64> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Error reading fitness config {}: {}", path.display(), e))?;
+    let mut weights = FitnessWeights::default();
+    for (term, weight) in overrides {
+        weights.set(term, weight);
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn breakdown() -> FitnessBreakdown {
+        FitnessBreakdown {
+            image_count: 12,
+            free_area_percentage: 7.5,
+            aspect_ratio_diff: 0.08,
+            cropped_percentage: 3.0,
+            row_height_variation: 0.2,
+            size_variation: 0.4,
+            hue_spread: 0.6,
+            orientation_imbalance: 0.25,
+            required_coverage: 0.3,
+            brightness_imbalance: 0.1,
+            adjacent_colour_similarity: 0.7,
+            palette_distance: 0.0,
+            near_duplicates: 0,
+        }
+    }
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("fitness_{}_{}.json", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn default_weights_reproduce_the_original_fitness() {
+        let b = breakdown();
+        let original = b.image_count as f64
+            / (1.0
+                + b.free_area_percentage
+                + b.aspect_ratio_diff * 10.0
+                + b.cropped_percentage
+                + b.row_height_variation * 10.0);
+        let weights = FitnessWeights::default();
+        assert!(weights.validate().is_ok());
+        assert!((weights.fitness(&b) - original).abs() < 1e-12);
+    }
+
+    #[test]
+    fn validate_rejects_negative_and_non_finite_weights() {
+        for weight in [-1.0, f64::NAN, f64::INFINITY] {
+            let mut weights = FitnessWeights::default();
+            weights.set(FitnessTerm::Whitespace, weight);
+            assert!(weights.validate().is_err(), "weight {} was accepted", weight);
+        }
+    }
+
+    #[test]
+    fn validate_needs_a_positive_reward_weight() {
+        let mut weights = FitnessWeights::default();
+        weights.set(FitnessTerm::ImageCount, 0.0);
+        assert!(weights.validate().is_err());
+        weights.set(FitnessTerm::RequiredCoverage, 1.0);
+        assert!(weights.validate().is_ok());
+    }
+
+    #[test]
+    fn zero_weight_turns_a_term_off() {
+        let mut weights = FitnessWeights::default();
+        weights.set(FitnessTerm::Whitespace, 0.0);
+        let b = breakdown();
+        let more_whitespace = FitnessBreakdown { free_area_percentage: 50.0, ..breakdown() };
+        assert_eq!(weights.fitness(&b), weights.fitness(&more_whitespace));
+        assert!(FitnessWeights::default().fitness(&more_whitespace) < FitnessWeights::default().fitness(&b));
+    }
+
+    #[test]
+    fn read_fitness_weights_overrides_the_given_terms() {
+        let path = write_config("overrides", r#"{"whitespace": 2, "colour_harmony": 5}"#);
+        let weights = read_fitness_weights(&path);
+        fs::remove_file(&path).unwrap();
+        let weights = weights.unwrap();
+        assert_eq!(weights.get(FitnessTerm::Whitespace), 2.0);
+        assert_eq!(weights.get(FitnessTerm::ColourHarmony), 5.0);
+        assert_eq!(weights.get(FitnessTerm::AspectDeviation), FitnessTerm::AspectDeviation.default_weight());
+    }
+
+    #[test]
+    fn read_fitness_weights_rejects_unknown_terms() {
+        let path = write_config("unknown", r#"{"whitespace": 2, "sharpness": 1}"#);
+        let weights = read_fitness_weights(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(weights.is_err());
+    }
+}
@@ -1,45 +1,188 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
 use std::collections::HashMap;
-use image::DynamicImage;
+use std::str::FromStr;
+use image::{DynamicImage, GenericImageView};
+use rect_packer::Rect;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
-use crate::packing::{pack_images, DESIRED_ASPECT_RATIO};
+use crate::fitness::FitnessWeights;
+use crate::image_handling::{delta_e, ColourStats};
+use crate::packing::{
+    fit_to_canvas, pack_images, slicing_layout, CutDirection, LayoutConfig, LayoutMode, PackedLayout, Placement, SliceNode,
+};
 
-#[derive(Clone)]
+/// Random number generator used throughout the GA. ChaCha is portable, so a seed
+/// reproduces the same run on every platform.
+pub type GaRng = ChaCha8Rng;
+
+/// Creates the RNG for one independent stream of a seeded run.
+///
+/// Work that is spread over rayon workers draws from its own stream (e.g. one per
+/// child of a generation), so results do not depend on thread scheduling.
+pub fn stream_rng(seed: u64, stream: u64) -> GaRng {
+    let mut rng = GaRng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    rng
+}
+
+/// The quantities `evaluate_individual` combines into the fitness value, one per
+/// [`FitnessTerm`](crate::fitness::FitnessTerm).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FitnessBreakdown {
+    pub image_count: usize,
+    pub free_area_percentage: f64,
+    pub aspect_ratio_diff: f64,
+    /// Share of the drawn images' content that is cut away by cropping, in percent.
+    pub cropped_percentage: f64,
+    /// Coefficient of variation of the row heights in the justified-rows mode.
+    pub row_height_variation: f64,
+    /// Coefficient of variation of the drawn image areas.
+    pub size_variation: f64,
+    /// Circular variance of the dominant hues of the images weighted by their
+    /// saturation, from 0 to 1.
+    pub hue_spread: f64,
+    /// Difference between the numbers of landscape and portrait images relative to
+    /// the image count.
+    pub orientation_imbalance: f64,
+    /// Share of the drawn area taken by the locked images.
+    pub required_coverage: f64,
+    /// Difference in lightness between the left and right and between the top and
+    /// bottom half of the collage, from 0 to 1.
+    pub brightness_imbalance: f64,
+    /// Mean [`ColourStats::similarity`] of the images that touch each other.
+    pub adjacent_colour_similarity: f64,
+    /// ΔE between the mean colour of the collage and the target colour, divided by 100.
+    pub palette_distance: f64,
+    /// Number of pairs of near-duplicate images in the collage.
+    pub near_duplicates: usize,
+}
+
+impl FitnessBreakdown {
+    /// The objectives of the Pareto mode, all maximised: the image count, the
+    /// negated free area percentage and the negated aspect ratio deviation.
+    pub fn objectives(&self) -> Vec<f64> {
+        vec![self.image_count as f64, -self.free_area_percentage, -self.aspect_ratio_diff]
+    }
+}
+
+/// The objectives of a collage; without a layout it is worse than any collage that has one.
+fn collage_objectives(breakdown: &FitnessBreakdown, layout: Option<&PackedLayout>) -> Vec<f64> {
+    match layout {
+        Some(_) => breakdown.objectives(),
+        None => vec![f64::NEG_INFINITY; 3],
+    }
+}
+
+/// A single collage: the chosen images in packing order.
+///
+/// Only the genome and its fitness are serialized (for checkpoints); the layout
+/// is restored by evaluating the individual again.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Individual {
     pub image_ids: Vec<u32>,
+    /// Scale factor of each chosen image relative to its loaded size.
+    pub scales: HashMap<u32, f64>,
     pub fitness: f64,
-    pub packed_layout: Option<(Vec<(u32, rect_packer::Rect)>, u32, u32)>,
+    pub breakdown: FitnessBreakdown,
+    #[serde(skip)]
+    pub packed_layout: Option<PackedLayout>,
 }
 
+/// Everything the genetic operators and the evaluation need to know about the problem.
+pub struct GaContext<'a> {
+    pub images: &'a HashMap<u32, DynamicImage>,
+    /// IDs the GA may choose from (excluded images removed), sorted ascending.
+    pub all_ids: Vec<u32>,
+    /// IDs that must be part of every individual.
+    pub locked: Vec<u32>,
+    pub min_images: usize,
+    pub max_images: usize,
+    /// Recombination operator of single collages.
+    pub crossover: CrossoverKind,
+    /// Bounds of the per-image scale genes; equal bounds fix the scale of every image.
+    pub scale_range: (f64, f64),
+    /// Number of collages every image is distributed over in partition mode.
+    pub pages: usize,
+    pub layout: &'a LayoutConfig,
+    pub fitness: &'a FitnessWeights,
+    /// Colour statistics of the images in `all_ids`.
+    pub colours: HashMap<u32, ColourStats>,
+    /// L*a*b* colour the palette of the collage is pulled toward.
+    pub target_colour: Option<[f64; 3]>,
+    /// Pairs of images that look alike, see [`crate::image_handling::near_duplicate_pairs`].
+    pub near_duplicates: Vec<(u32, u32)>,
+}
+
+/// A candidate solution the GA in [`crate::evolution`] can evolve.
+pub trait Genome: Clone + Send + Sync {
+    fn random(ctx: &GaContext, rng: &mut GaRng) -> Self;
+    fn crossover(&self, other: &Self, ctx: &GaContext, rng: &mut GaRng) -> Self;
+    fn mutate(&mut self, ctx: &GaContext, rng: &mut GaRng);
+    /// Restores the constraints of a genome copied unchanged into the next generation.
+    fn repair(&mut self, ctx: &GaContext, rng: &mut GaRng);
+    fn evaluate(&mut self, ctx: &GaContext);
+    fn fitness(&self) -> f64;
+    /// How different two genomes are, from 0 (identical) to 1.
+    fn distance(&self, other: &Self) -> f64;
+    /// The objectives the Pareto mode optimizes, all maximised. Defaults to the fitness alone.
+    fn objectives(&self) -> Vec<f64> {
+        vec![self.fitness()]
+    }
+}
+
+/// Creates an individual with a random number of images within the limits.
+/// The `locked` images are always part of it.
 pub fn create_random_individual(
-    all_images: &[(u32, DynamicImage)],
+    all_ids: &[u32],
+    locked: &[u32],
     min_images: usize,
     max_images: usize,
     rng: &mut impl Rng,
 ) -> Individual {
-    let num_images = (rng.gen_range(min_images..=max_images)).min(all_images.len());
-    let mut shuffled = all_images.iter().map(|(id, _)| *id).collect::<Vec<u32>>();
+    let num_images = (rng.gen_range(min_images..=max_images)).min(all_ids.len()).max(locked.len());
+    let mut shuffled: Vec<u32> = all_ids.iter().copied().filter(|id| !locked.contains(id)).collect();
     shuffled.shuffle(rng);
-    shuffled.truncate(num_images);
+    shuffled.truncate(num_images - locked.len());
+
+    // Put the locked images at random positions so they are not always packed first
+    let mut image_ids = shuffled;
+    for &id in locked {
+        let pos = rng.gen_range(0..=image_ids.len());
+        image_ids.insert(pos, id);
+    }
 
     Individual {
-        image_ids: shuffled,
+        image_ids,
+        scales: HashMap::new(),
         fitness: 0.0,
+        breakdown: FitnessBreakdown::default(),
         packed_layout: None,
     }
 }
 
+/// Adds missing `locked` images and then adds or removes random unlocked images
+/// until the count is within `min_images..=max_images`.
 pub fn enforce_image_limits(
     image_ids: &mut Vec<u32>,
-    all_images: &[(u32, DynamicImage)],
+    all_ids: &[u32],
+    locked: &[u32],
     min_images: usize,
     max_images: usize,
     rng: &mut impl Rng,
 ) {
+    // Locked images are part of every individual
+    for &id in locked {
+        if !image_ids.contains(&id) {
+            image_ids.push(id);
+        }
+    }
+
     // Ensure at least min_images
     while image_ids.len() < min_images {
-        let mut available: Vec<u32> = all_images.iter().map(|(id, _)| *id).collect();
+        let mut available: Vec<u32> = all_ids.to_vec();
         available.retain(|x| !image_ids.contains(x));
         if available.is_empty() {
             break;
@@ -49,45 +192,240 @@ pub fn enforce_image_limits(
         }
     }
 
-    // Ensure no more than max_images
+    // Ensure no more than max_images, never dropping a locked image
     while image_ids.len() > max_images {
-        let remove_idx = rng.gen_range(0..image_ids.len());
-        image_ids.remove(remove_idx);
+        let removable: Vec<usize> = (0..image_ids.len()).filter(|&i| !locked.contains(&image_ids[i])).collect();
+        match removable.choose(rng) {
+            Some(&remove_idx) => {
+                image_ids.remove(remove_idx);
+            }
+            None => break,
+        }
     }
 }
 
-pub fn evaluate_individual(
-    indiv: &mut Individual,
-    all_images_map: &HashMap<u32, DynamicImage>,
-) {
-    let (packed_locations, w, h) = pack_images(&indiv.image_ids, all_images_map);
+pub fn evaluate_individual(indiv: &mut Individual, ctx: &GaContext) {
+    let layout = pack_images(&indiv.image_ids, &indiv.scales, ctx.images, ctx.layout);
+    match score_layout(layout, indiv.image_ids.len(), ctx) {
+        Some((fitness, breakdown, layout)) => {
+            indiv.fitness = fitness;
+            indiv.breakdown = breakdown;
+            indiv.packed_layout = Some(layout);
+        }
+        None => {
+            indiv.fitness = 0.0;
+            indiv.breakdown = FitnessBreakdown::default();
+            indiv.packed_layout = None;
+        }
+    }
+}
+
+/// Rates the layout of a collage with `image_count` images and fits it to the
+/// canvas, if any. `None` if nothing could be laid out.
+fn score_layout(layout: PackedLayout, image_count: usize, ctx: &GaContext) -> Option<(f64, FitnessBreakdown, PackedLayout)> {
+    let config = ctx.layout;
+    let (packed_locations, w, h) = layout;
     if packed_locations.is_empty() || w == 0 || h == 0 {
-        indiv.fitness = 0.0;
-        indiv.packed_layout = None;
-        return;
+        return None;
     }
+    // In fill mode the layout has the canvas size, so free area measures coverage of the fixed frame
     let collage_area = (w as u64) * (h as u64);
     let total_packed_area: u64 = packed_locations
         .iter()
-        .map(|(_, rect)| rect.width as u64 * rect.height as u64)
+        .map(|placement| placement.rect.width as u64 * placement.rect.height as u64)
         .sum();
     let free_area = collage_area.saturating_sub(total_packed_area);
     let free_area_percentage = (free_area as f64 / collage_area as f64) * 100.0;
     let aspect_ratio = if h == 0 { 9999.9 } else { w as f64 / h as f64 };
-    let aspect_ratio_diff = (aspect_ratio - DESIRED_ASPECT_RATIO).abs();
+    let aspect_ratio_diff = (aspect_ratio - config.target_aspect_ratio()).abs();
 
-    let image_count_factor = indiv.image_ids.len() as f64;
-    // Fitness function considers number of images, free area, and aspect ratio deviation
-    let fitness = image_count_factor / (1.0 + free_area_percentage + aspect_ratio_diff * 10.0);
+    let cropped_percentage = cropped_percentage(&packed_locations);
+    let row_height_variation = match config.mode {
+        LayoutMode::JustifiedRows => row_height_variation(&packed_locations),
+        _ => 0.0,
+    };
 
-    indiv.fitness = fitness;
-    indiv.packed_layout = Some((packed_locations, w, h));
+    let breakdown = FitnessBreakdown {
+        image_count,
+        free_area_percentage,
+        aspect_ratio_diff,
+        cropped_percentage,
+        row_height_variation,
+        size_variation: size_variation(&packed_locations),
+        hue_spread: hue_spread(&packed_locations, &ctx.colours),
+        orientation_imbalance: orientation_imbalance(&packed_locations),
+        required_coverage: required_coverage(&packed_locations, &ctx.locked),
+        brightness_imbalance: brightness_imbalance(&packed_locations, &ctx.colours, w, h),
+        adjacent_colour_similarity: adjacent_colour_similarity(&packed_locations, &ctx.colours, config.padding),
+        palette_distance: palette_distance(&packed_locations, &ctx.colours, ctx.target_colour),
+        near_duplicates: near_duplicates(&packed_locations, &ctx.near_duplicates),
+    };
+    let fitness = ctx.fitness.fitness(&breakdown);
+    let layout = match config.canvas {
+        Some((canvas_w, canvas_h)) if !config.fill_canvas => fit_to_canvas((packed_locations, w, h), canvas_w, canvas_h, config.margin),
+        _ => (packed_locations, w, h),
+    };
+    Some((fitness, breakdown, layout))
+}
+
+/// Content cut away by cropping relative to the uncropped size of the drawn images, in percent.
+fn cropped_percentage(placements: &[Placement]) -> f64 {
+    let mut full_area = 0.0;
+    let mut lost_area = 0.0;
+    for placement in placements {
+        let drawn = placement.rect.width as f64 * placement.rect.height as f64;
+        let kept = placement.crop.map_or(1.0, |crop| crop.width * crop.height);
+        full_area += drawn / kept;
+        lost_area += drawn / kept - drawn;
+    }
+    if full_area > 0.0 { lost_area / full_area * 100.0 } else { 0.0 }
+}
+
+/// Standard deviation of the row heights divided by their mean, taking the
+/// images that share a top edge as one row.
+fn row_height_variation(placements: &[Placement]) -> f64 {
+    let mut rows: Vec<(i32, i32)> = placements.iter().map(|p| (p.rect.y, p.rect.height)).collect();
+    rows.sort();
+    rows.dedup_by_key(|(y, _)| *y);
+    if rows.len() < 2 {
+        return 0.0;
+    }
+    let n = rows.len() as f64;
+    let mean = rows.iter().map(|(_, h)| *h as f64).sum::<f64>() / n;
+    let variance = rows.iter().map(|(_, h)| (*h as f64 - mean).powi(2)).sum::<f64>() / n;
+    variance.sqrt() / mean
+}
+
+/// Standard deviation of the drawn image areas divided by their mean.
+fn size_variation(placements: &[Placement]) -> f64 {
+    let areas: Vec<f64> = placements.iter().map(|p| p.rect.width as f64 * p.rect.height as f64).collect();
+    let n = areas.len() as f64;
+    let mean = areas.iter().sum::<f64>() / n;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let variance = areas.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / n;
+    variance.sqrt() / mean
+}
+
+/// One minus the length of the mean hue vector, each image's dominant hue weighted
+/// by its saturation: 0 if all coloured images share a hue, up to 1 for evenly spread hues.
+fn hue_spread(placements: &[Placement], colours: &HashMap<u32, ColourStats>) -> f64 {
+    let (mut x, mut y, mut total) = (0.0, 0.0, 0.0);
+    for stats in placements.iter().filter_map(|p| colours.get(&p.id)) {
+        let angle = stats.dominant_hue.to_radians();
+        x += stats.saturation * angle.cos();
+        y += stats.saturation * angle.sin();
+        total += stats.saturation;
+    }
+    if total > 0.0 { 1.0 - x.hypot(y) / total } else { 0.0 }
+}
+
+/// Difference between the numbers of landscape and portrait images as drawn,
+/// relative to the image count; square images count as neither.
+fn orientation_imbalance(placements: &[Placement]) -> f64 {
+    let balance: i64 = placements
+        .iter()
+        .map(|p| (p.rect.width as i64 - p.rect.height as i64).signum())
+        .sum();
+    balance.unsigned_abs() as f64 / placements.len() as f64
+}
+
+/// Share of the drawn image area taken by the `locked` images.
+fn required_coverage(placements: &[Placement], locked: &[u32]) -> f64 {
+    let area = |p: &Placement| p.rect.width as f64 * p.rect.height as f64;
+    let total: f64 = placements.iter().map(area).sum();
+    let covered: f64 = placements
+        .iter()
+        .map(|p| if locked.contains(&p.id) { area(p) } else { 0.0 })
+        .sum();
+    if total > 0.0 { covered / total } else { 0.0 }
+}
+
+/// Mean difference in lightness (L* / 100) between the left and right and between
+/// the top and bottom half of a `width` x `height` collage. Each image belongs to
+/// the halves of its centre and counts with its area.
+fn brightness_imbalance(placements: &[Placement], colours: &HashMap<u32, ColourStats>, width: u32, height: u32) -> f64 {
+    // Sum of area * L* and sum of area of the left, right, top and bottom half
+    let mut halves = [(0.0, 0.0); 4];
+    for placement in placements {
+        let Some(stats) = colours.get(&placement.id) else {
+            continue;
+        };
+        let rect = &placement.rect;
+        let area = rect.width as f64 * rect.height as f64;
+        let horizontal = if ((rect.x * 2 + rect.width) as f64) < width as f64 { 0 } else { 1 };
+        let vertical = if ((rect.y * 2 + rect.height) as f64) < height as f64 { 2 } else { 3 };
+        for half in [horizontal, vertical] {
+            halves[half].0 += area * stats.mean_lab[0];
+            halves[half].1 += area;
+        }
+    }
+    let difference = |a: (f64, f64), b: (f64, f64)| {
+        if a.1 > 0.0 && b.1 > 0.0 {
+            (a.0 / a.1 - b.0 / b.1).abs() / 100.0
+        } else {
+            0.0
+        }
+    };
+    (difference(halves[0], halves[1]) + difference(halves[2], halves[3])) / 2.0
+}
+
+/// Whether two rects share a stretch of an edge, at most `gap` pixels apart.
+fn touching(a: &Rect, b: &Rect, gap: i32) -> bool {
+    let overlap = |a0: i32, a_len: i32, b0: i32, b_len: i32| a0.max(b0) < (a0 + a_len).min(b0 + b_len);
+    let close = |a_end: i32, b_start: i32| (0..=gap).contains(&(b_start - a_end));
+    let side_by_side = overlap(a.y, a.height, b.y, b.height) && (close(a.x + a.width, b.x) || close(b.x + b.width, a.x));
+    let stacked = overlap(a.x, a.width, b.x, b.width) && (close(a.y + a.height, b.y) || close(b.y + b.height, a.y));
+    side_by_side || stacked
+}
+
+/// Mean colour similarity of the pairs of images that touch, 0 if none do.
+fn adjacent_colour_similarity(placements: &[Placement], colours: &HashMap<u32, ColourStats>, padding: u32) -> f64 {
+    let gap = padding as i32 + 1;
+    let mut total = 0.0;
+    let mut pairs = 0;
+    for (i, a) in placements.iter().enumerate() {
+        for b in &placements[i + 1..] {
+            if let (true, Some(stats_a), Some(stats_b)) = (touching(&a.rect, &b.rect, gap), colours.get(&a.id), colours.get(&b.id)) {
+                total += stats_a.similarity(stats_b);
+                pairs += 1;
+            }
+        }
+    }
+    if pairs > 0 { total / pairs as f64 } else { 0.0 }
+}
+
+/// ΔE / 100 between the mean colour of the images, weighted by area, and `target`; 0 without a target.
+fn palette_distance(placements: &[Placement], colours: &HashMap<u32, ColourStats>, target: Option<[f64; 3]>) -> f64 {
+    let Some(target) = target else {
+        return 0.0;
+    };
+    let mut sum = [0.0; 3];
+    let mut total_area = 0.0;
+    for placement in placements {
+        if let Some(stats) = colours.get(&placement.id) {
+            let area = placement.rect.width as f64 * placement.rect.height as f64;
+            for (s, v) in sum.iter_mut().zip(stats.mean_lab) {
+                *s += area * v;
+            }
+            total_area += area;
+        }
+    }
+    if total_area > 0.0 { delta_e(sum.map(|s| s / total_area), target) / 100.0 } else { 0.0 }
+}
+
+/// Number of the `pairs` whose images are both placed.
+fn near_duplicates(placements: &[Placement], pairs: &[(u32, u32)]) -> usize {
+    let placed = |id: u32| placements.iter().any(|p| p.id == id);
+    pairs.iter().filter(|&&(a, b)| placed(a) && placed(b)).count()
 }
 
 pub fn crossover(
     parent1: &Individual,
     parent2: &Individual,
-    all_images: &[(u32, DynamicImage)],
+    all_ids: &[u32],
+    locked: &[u32],
     min_images: usize,
     max_images: usize,
     rng: &mut impl Rng
@@ -98,7 +436,9 @@ pub fn crossover(
     if p1_len == 0 && p2_len == 0 {
         return Individual {
             image_ids: vec![],
+            scales: HashMap::new(),
             fitness: 0.0,
+            breakdown: FitnessBreakdown::default(),
             packed_layout: None,
         };
     }
@@ -112,18 +452,21 @@ pub fn crossover(
     child_ids.sort();
     child_ids.dedup();
 
-    enforce_image_limits(&mut child_ids, all_images, min_images, max_images, rng);
+    enforce_image_limits(&mut child_ids, all_ids, locked, min_images, max_images, rng);
 
     Individual {
         image_ids: child_ids,
+        scales: HashMap::new(),
         fitness: 0.0,
+        breakdown: FitnessBreakdown::default(),
         packed_layout: None,
     }
 }
 
 pub fn mutate(
     indiv: &mut Individual,
-    all_images: &[(u32, DynamicImage)],
+    all_ids: &[u32],
+    locked: &[u32],
     min_images: usize,
     max_images: usize,
     rng: &mut impl Rng
@@ -136,20 +479,24 @@ pub fn mutate(
 
     if roll < 0.33 && indiv.image_ids.len() < max_images {
         // Add a new image
-        let mut available: Vec<u32> = all_images.iter().map(|(id, _)| *id).collect();
+        let mut available: Vec<u32> = all_ids.to_vec();
         available.retain(|x| !indiv.image_ids.contains(x));
         if let Some(&new_id) = available.choose(rng) {
             indiv.image_ids.push(new_id);
         }
-    } else if roll < 0.66 && indiv.image_ids.len() > min_images {
-        // Remove an image
-        let remove_idx = rng.gen_range(0..indiv.image_ids.len());
-        indiv.image_ids.remove(remove_idx);
     } else {
-        // Replace an image
-        if !all_images.is_empty() {
-            let idx = rng.gen_range(0..indiv.image_ids.len());
-            let mut available: Vec<u32> = all_images.iter().map(|(id, _)| *id).collect();
+        // Locked images are never removed or replaced
+        let unlocked: Vec<usize> = (0..indiv.image_ids.len())
+            .filter(|&i| !locked.contains(&indiv.image_ids[i]))
+            .collect();
+        if roll < 0.66 && indiv.image_ids.len() > min_images {
+            // Remove an image
+            if let Some(&remove_idx) = unlocked.choose(rng) {
+                indiv.image_ids.remove(remove_idx);
+            }
+        } else if let Some(&idx) = unlocked.choose(rng) {
+            // Replace an image
+            let mut available: Vec<u32> = all_ids.to_vec();
             available.retain(|x| !indiv.image_ids.contains(x));
             if let Some(&new_id) = available.choose(rng) {
                 indiv.image_ids[idx] = new_id;
@@ -157,5 +504,814 @@ pub fn mutate(
         }
     }
 
-    enforce_image_limits(&mut indiv.image_ids, all_images, min_images, max_images, rng);
+    enforce_image_limits(&mut indiv.image_ids, all_ids, locked, min_images, max_images, rng);
+}
+
+fn random_scale((min_scale, max_scale): (f64, f64), rng: &mut impl Rng) -> f64 {
+    if min_scale < max_scale {
+        rng.gen_range(min_scale..=max_scale)
+    } else {
+        min_scale
+    }
+}
+
+/// Drops the scale genes of images the individual no longer uses and gives every
+/// image without one a random scale within `scale_range`.
+pub fn sync_scales(indiv: &mut Individual, scale_range: (f64, f64), rng: &mut impl Rng) {
+    let image_ids = &indiv.image_ids;
+    indiv.scales.retain(|id, _| image_ids.contains(id));
+    for id in &indiv.image_ids {
+        if !indiv.scales.contains_key(id) {
+            indiv.scales.insert(*id, random_scale(scale_range, rng));
+        }
+    }
+}
+
+/// Gives every image of `child` the scale it has in one of the parents, picking
+/// a random parent for images both of them use.
+pub fn inherit_scales(
+    child: &mut Individual,
+    parent1: &Individual,
+    parent2: &Individual,
+    scale_range: (f64, f64),
+    rng: &mut impl Rng,
+) {
+    let varies = scale_range.0 < scale_range.1;
+    for id in &child.image_ids {
+        let scale = match (parent1.scales.get(id), parent2.scales.get(id)) {
+            (Some(&s1), Some(&s2)) if varies => if rng.gen_bool(0.5) { s1 } else { s2 },
+            (Some(&s), _) | (None, Some(&s)) => s,
+            (None, None) => continue,
+        };
+        child.scales.insert(*id, scale);
+    }
+    sync_scales(child, scale_range, rng);
+}
+
+/// Grows or shrinks one random image by up to 25%, staying within `scale_range`.
+pub fn mutate_scale(indiv: &mut Individual, scale_range: (f64, f64), rng: &mut impl Rng) {
+    if let Some(id) = indiv.image_ids.choose(rng) {
+        let factor = rng.gen_range(0.8..=1.25);
+        let scale = indiv.scales.entry(*id).or_insert(1.0);
+        *scale = (*scale * factor).clamp(scale_range.0, scale_range.1);
+    }
+}
+
+/// How two individuals are recombined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrossoverKind {
+    /// Order crossover: a slice of the first parent, the rest in the order of the second.
+    #[default]
+    Ox,
+    /// Partially mapped crossover: a slice of the first parent, the rest at the
+    /// positions of the second where possible.
+    Pmx,
+    /// Cycle crossover: every image keeps the position it has in one of the parents.
+    Cx,
+    /// One-point crossover of the selections; the order is lost and only changed by mutation.
+    Cut,
+}
+
+impl FromStr for CrossoverKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ox" => Ok(CrossoverKind::Ox),
+            "pmx" => Ok(CrossoverKind::Pmx),
+            "cx" => Ok(CrossoverKind::Cx),
+            "cut" => Ok(CrossoverKind::Cut),
+            _ => Err(format!("Unknown crossover: {}", s)),
+        }
+    }
+}
+
+/// The individual as a permutation of the whole pool: its images in packing
+/// order, followed by the unused ones in ascending order.
+fn pool_permutation(indiv: &Individual, all_ids: &[u32]) -> Vec<u32> {
+    let mut permutation = indiv.image_ids.clone();
+    permutation.extend(all_ids.iter().filter(|id| !indiv.image_ids.contains(id)));
+    permutation
+}
+
+/// Random slice `i..=j` of a sequence of length `len` (at least 1).
+fn random_slice(len: usize, rng: &mut impl Rng) -> (usize, usize) {
+    let i = rng.gen_range(0..len);
+    let j = rng.gen_range(0..len);
+    (i.min(j), i.max(j))
+}
+
+/// Order crossover (OX) of two permutations of the same IDs.
+pub fn ordered_crossover(parent1: &[u32], parent2: &[u32], rng: &mut impl Rng) -> Vec<u32> {
+    let len = parent1.len();
+    if len < 2 {
+        return parent1.to_vec();
+    }
+    let (i, j) = random_slice(len, rng);
+    let slice = &parent1[i..=j];
+
+    // Fill the positions after the slice, wrapping around, with the other IDs in
+    // the order they follow the slice in the second parent
+    let mut child = parent1.to_vec();
+    let rest = (1..=len).map(|k| parent2[(j + k) % len]).filter(|id| !slice.contains(id));
+    for (k, id) in rest.enumerate() {
+        child[(j + 1 + k) % len] = id;
+    }
+    child
+}
+
+/// Partially mapped crossover (PMX) of two permutations of the same IDs.
+pub fn partially_mapped_crossover(parent1: &[u32], parent2: &[u32], rng: &mut impl Rng) -> Vec<u32> {
+    let len = parent1.len();
+    if len < 2 {
+        return parent1.to_vec();
+    }
+    let (i, j) = random_slice(len, rng);
+    let position2: HashMap<u32, usize> = parent2.iter().enumerate().map(|(pos, id)| (*id, pos)).collect();
+
+    let mut child = parent2.to_vec();
+    child[i..=j].copy_from_slice(&parent1[i..=j]);
+    // Every ID the slice displaced moves to where the mapping chain leaves the slice
+    for (k, &id) in parent2.iter().enumerate().take(j + 1).skip(i) {
+        if parent1[i..=j].contains(&id) {
+            continue;
+        }
+        let mut pos = k;
+        while (i..=j).contains(&pos) {
+            pos = position2[&parent1[pos]];
+        }
+        child[pos] = id;
+    }
+    child
+}
+
+/// Cycle crossover (CX) of two permutations of the same IDs, taking the cycles
+/// alternately from both parents.
+pub fn cycle_crossover(parent1: &[u32], parent2: &[u32]) -> Vec<u32> {
+    let position1: HashMap<u32, usize> = parent1.iter().enumerate().map(|(pos, id)| (*id, pos)).collect();
+    let mut child: Vec<Option<u32>> = vec![None; parent1.len()];
+    let mut from_first = true;
+    for start in 0..parent1.len() {
+        if child[start].is_some() {
+            continue;
+        }
+        let source = if from_first { parent1 } else { parent2 };
+        let mut pos = start;
+        loop {
+            child[pos] = Some(source[pos]);
+            pos = position1[&parent2[pos]];
+            if pos == start {
+                break;
+            }
+        }
+        from_first = !from_first;
+    }
+    child.into_iter().map(|id| id.unwrap()).collect()
+}
+
+/// Recombines the pool permutations of both parents with an order-preserving
+/// crossover. The child uses the first images of the result, as many as one of
+/// the parents or a count in between.
+pub fn permutation_crossover(
+    parent1: &Individual,
+    parent2: &Individual,
+    kind: CrossoverKind,
+    all_ids: &[u32],
+    rng: &mut impl Rng,
+) -> Individual {
+    let perm1 = pool_permutation(parent1, all_ids);
+    let perm2 = pool_permutation(parent2, all_ids);
+    let mut child_ids = match kind {
+        CrossoverKind::Pmx => partially_mapped_crossover(&perm1, &perm2, rng),
+        CrossoverKind::Cx => cycle_crossover(&perm1, &perm2),
+        _ => ordered_crossover(&perm1, &perm2, rng),
+    };
+    let (len1, len2) = (parent1.image_ids.len(), parent2.image_ids.len());
+    child_ids.truncate(rng.gen_range(len1.min(len2)..=len1.max(len2)));
+
+    Individual {
+        image_ids: child_ids,
+        scales: HashMap::new(),
+        fitness: 0.0,
+        breakdown: FitnessBreakdown::default(),
+        packed_layout: None,
+    }
+}
+
+/// Changes the packing order: swaps two images, moves one to another position
+/// or reverses a run of images, with equal probability.
+pub fn mutate_order(indiv: &mut Individual, rng: &mut impl Rng) {
+    let len = indiv.image_ids.len();
+    if len < 2 {
+        return;
+    }
+    let (i, j) = random_slice(len, rng);
+    match rng.gen_range(0..3) {
+        0 => indiv.image_ids.swap(i, j),
+        1 => {
+            let id = indiv.image_ids.remove(i);
+            indiv.image_ids.insert(j, id);
+        }
+        _ => indiv.image_ids[i..=j].reverse(),
+    }
+}
+
+impl Genome for Individual {
+    fn random(ctx: &GaContext, rng: &mut GaRng) -> Self {
+        let mut indiv = create_random_individual(&ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng);
+        sync_scales(&mut indiv, ctx.scale_range, rng);
+        indiv
+    }
+
+    fn crossover(&self, other: &Self, ctx: &GaContext, rng: &mut GaRng) -> Self {
+        let mut child = match ctx.crossover {
+            CrossoverKind::Cut => crossover(self, other, &ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng),
+            kind => {
+                let mut child = permutation_crossover(self, other, kind, &ctx.all_ids, rng);
+                enforce_image_limits(&mut child.image_ids, &ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng);
+                child
+            }
+        };
+        inherit_scales(&mut child, self, other, ctx.scale_range, rng);
+        child
+    }
+
+    fn mutate(&mut self, ctx: &GaContext, rng: &mut GaRng) {
+        // With free scales, half of the mutations resize an image; half of the
+        // remaining ones change the packing order instead of the selection
+        if ctx.scale_range.0 < ctx.scale_range.1 && rng.gen_bool(0.5) {
+            mutate_scale(self, ctx.scale_range, rng);
+        } else if rng.gen_bool(0.5) {
+            mutate_order(self, rng);
+        } else {
+            mutate(self, &ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng);
+            sync_scales(self, ctx.scale_range, rng);
+        }
+    }
+
+    fn repair(&mut self, ctx: &GaContext, rng: &mut GaRng) {
+        enforce_image_limits(&mut self.image_ids, &ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng);
+        sync_scales(self, ctx.scale_range, rng);
+    }
+
+    fn evaluate(&mut self, ctx: &GaContext) {
+        evaluate_individual(self, ctx)
+    }
+
+    fn fitness(&self) -> f64 {
+        self.fitness
+    }
+
+    /// The share of positions of the packing sequence holding different images.
+    fn distance(&self, other: &Self) -> f64 {
+        sequence_distance(&self.image_ids, &other.image_ids)
+    }
+
+    fn objectives(&self) -> Vec<f64> {
+        collage_objectives(&self.breakdown, self.packed_layout.as_ref())
+    }
+}
+
+/// The share of positions of the longer sequence that hold a different image in the other.
+fn sequence_distance(a: &[u32], b: &[u32]) -> f64 {
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    let same = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    1.0 - same as f64 / len as f64
+}
+
+/// A genome of a single collage, as evolved by [`CollageOptimizer::run`](crate::CollageOptimizer::run).
+pub trait CollageGenome: Genome + Serialize + DeserializeOwned {
+    /// Whether this genome describes collages of the layout `mode`.
+    fn supports_mode(mode: LayoutMode) -> bool;
+    /// The collage as an [`Individual`] with its images in layout order, for rendering and saving.
+    fn into_individual(self) -> Individual;
+}
+
+impl CollageGenome for Individual {
+    fn supports_mode(mode: LayoutMode) -> bool {
+        mode != LayoutMode::Slicing
+    }
+
+    fn into_individual(self) -> Individual {
+        self
+    }
+}
+
+/// A single collage as a slicing tree ([`LayoutMode::Slicing`]): the cuts are
+/// evolved together with the images, which fill the collage edge to edge.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SlicingTree {
+    pub root: SliceNode,
+    pub fitness: f64,
+    pub breakdown: FitnessBreakdown,
+    #[serde(skip)]
+    pub packed_layout: Option<PackedLayout>,
+}
+
+fn random_direction(rng: &mut impl Rng) -> CutDirection {
+    if rng.gen_bool(0.5) {
+        CutDirection::Vertical
+    } else {
+        CutDirection::Horizontal
+    }
+}
+
+/// Puts `id` next to or below a random leaf of `tree`, splitting the part of that leaf.
+pub fn insert_leaf(tree: &mut SliceNode, id: u32, rng: &mut impl Rng) {
+    let index = rng.gen_range(0..tree.leaves().len());
+    let direction = random_direction(rng);
+    let new_first = rng.gen_bool(0.5);
+    let leaf = tree.leaf_mut(index).unwrap();
+    let old = std::mem::replace(leaf, SliceNode::Leaf(id));
+    *leaf = if new_first {
+        SliceNode::cut(direction, SliceNode::Leaf(id), old)
+    } else {
+        SliceNode::cut(direction, old, SliceNode::Leaf(id))
+    };
+}
+
+/// A random slicing tree over `ids`, `None` if there are none.
+pub fn random_slicing_tree(ids: &[u32], rng: &mut impl Rng) -> Option<SliceNode> {
+    let (&first, rest) = ids.split_first()?;
+    let mut tree = SliceNode::Leaf(first);
+    for &id in rest {
+        insert_leaf(&mut tree, id, rng);
+    }
+    Some(tree)
+}
+
+/// Subtree crossover: a random subtree of `other` replaces a random subtree of
+/// `tree`, after its images have been taken out of `tree` to keep them unique.
+pub fn subtree_crossover(tree: &SliceNode, other: &SliceNode, rng: &mut impl Rng) -> SliceNode {
+    let donor = other.node(rng.gen_range(0..other.node_count())).unwrap().clone();
+    match tree.clone().without(&donor.leaves()) {
+        Some(mut child) => {
+            let index = rng.gen_range(0..child.node_count());
+            *child.node_mut(index).unwrap() = donor;
+            child
+        }
+        None => donor,
+    }
+}
+
+/// Applies [`enforce_image_limits`] to the images of `tree`: missing images are
+/// inserted next to random leaves and surplus ones cut out.
+pub fn enforce_tree_limits(tree: SliceNode, ctx: &GaContext, rng: &mut impl Rng) -> SliceNode {
+    let current = tree.leaves();
+    let mut ids = current.clone();
+    enforce_image_limits(&mut ids, &ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng);
+
+    let removed: Vec<u32> = current.iter().copied().filter(|id| !ids.contains(id)).collect();
+    let added: Vec<u32> = ids.iter().copied().filter(|id| !current.contains(id)).collect();
+    match tree.without(&removed) {
+        Some(mut tree) => {
+            for id in added {
+                insert_leaf(&mut tree, id, rng);
+            }
+            tree
+        }
+        None => random_slicing_tree(&ids, rng).expect("the image limits keep at least one image"),
+    }
+}
+
+/// Changes the cuts or the images of `tree`: flips a cut, swaps or moves two
+/// images, or adds, removes or replaces an image.
+pub fn mutate_slicing_tree(tree: SliceNode, ctx: &GaContext, rng: &mut impl Rng) -> SliceNode {
+    let mut tree = tree;
+    let leaves = tree.leaves();
+    let unlocked: Vec<u32> = leaves.iter().copied().filter(|id| !ctx.locked.contains(id)).collect();
+    let unused: Vec<u32> = ctx.all_ids.iter().copied().filter(|id| !leaves.contains(id)).collect();
+    match rng.gen_range(0..6) {
+        0 if leaves.len() > 1 => {
+            // Flip a cut
+            if let Some(SliceNode::Cut { direction, .. }) = tree.cut_mut(rng.gen_range(0..leaves.len() - 1)) {
+                *direction = direction.flipped();
+            }
+        }
+        1 if leaves.len() > 1 => {
+            // Swap two images
+            let picked: Vec<usize> = rand::seq::index::sample(rng, leaves.len(), 2).into_vec();
+            *tree.leaf_mut(picked[0]).unwrap() = SliceNode::Leaf(leaves[picked[1]]);
+            *tree.leaf_mut(picked[1]).unwrap() = SliceNode::Leaf(leaves[picked[0]]);
+        }
+        2 if leaves.len() > 1 => {
+            // Move an image next to another one
+            let id = *leaves.choose(rng).unwrap();
+            tree = tree.without(&[id]).unwrap();
+            insert_leaf(&mut tree, id, rng);
+        }
+        3 if leaves.len() < ctx.max_images && !unused.is_empty() => {
+            insert_leaf(&mut tree, *unused.choose(rng).unwrap(), rng);
+        }
+        4 if leaves.len() > ctx.min_images && leaves.len() > 1 && !unlocked.is_empty() => {
+            tree = tree.without(&[*unlocked.choose(rng).unwrap()]).unwrap();
+        }
+        _ => {
+            // Replace an image, keeping the cuts
+            if let (Some(&old), Some(&new)) = (unlocked.choose(rng), unused.choose(rng)) {
+                let index = leaves.iter().position(|&id| id == old).unwrap();
+                *tree.leaf_mut(index).unwrap() = SliceNode::Leaf(new);
+            }
+        }
+    }
+    enforce_tree_limits(tree, ctx, rng)
+}
+
+impl Genome for SlicingTree {
+    fn random(ctx: &GaContext, rng: &mut GaRng) -> Self {
+        let ids = create_random_individual(&ctx.all_ids, &ctx.locked, ctx.min_images, ctx.max_images, rng).image_ids;
+        SlicingTree {
+            root: random_slicing_tree(&ids, rng).expect("the image limits keep at least one image"),
+            fitness: 0.0,
+            breakdown: FitnessBreakdown::default(),
+            packed_layout: None,
+        }
+    }
+
+    fn crossover(&self, other: &Self, ctx: &GaContext, rng: &mut GaRng) -> Self {
+        let child = subtree_crossover(&self.root, &other.root, rng);
+        SlicingTree {
+            root: enforce_tree_limits(child, ctx, rng),
+            fitness: 0.0,
+            breakdown: FitnessBreakdown::default(),
+            packed_layout: None,
+        }
+    }
+
+    fn mutate(&mut self, ctx: &GaContext, rng: &mut GaRng) {
+        self.root = mutate_slicing_tree(self.root.clone(), ctx, rng);
+    }
+
+    fn repair(&mut self, ctx: &GaContext, rng: &mut GaRng) {
+        self.root = enforce_tree_limits(self.root.clone(), ctx, rng);
+    }
+
+    fn evaluate(&mut self, ctx: &GaContext) {
+        let ids = self.root.leaves();
+        let sizes: HashMap<u32, (u32, u32)> = ids
+            .iter()
+            .filter_map(|id| ctx.images.get(id).map(|img| (*id, img.dimensions())))
+            .collect();
+        let layout = slicing_layout(&self.root, &sizes, ctx.layout);
+        match score_layout(layout, ids.len(), ctx) {
+            Some((fitness, breakdown, layout)) => {
+                self.fitness = fitness;
+                self.breakdown = breakdown;
+                self.packed_layout = Some(layout);
+            }
+            None => {
+                self.fitness = 0.0;
+                self.breakdown = FitnessBreakdown::default();
+                self.packed_layout = None;
+            }
+        }
+    }
+
+    fn fitness(&self) -> f64 {
+        self.fitness
+    }
+
+    /// The share of positions of the image sequence holding different images.
+    fn distance(&self, other: &Self) -> f64 {
+        sequence_distance(&self.root.leaves(), &other.root.leaves())
+    }
+
+    fn objectives(&self) -> Vec<f64> {
+        collage_objectives(&self.breakdown, self.packed_layout.as_ref())
+    }
+}
+
+impl CollageGenome for SlicingTree {
+    fn supports_mode(mode: LayoutMode) -> bool {
+        mode == LayoutMode::Slicing
+    }
+
+    fn into_individual(self) -> Individual {
+        let image_ids = self.root.leaves();
+        Individual {
+            scales: image_ids.iter().map(|&id| (id, 1.0)).collect(),
+            image_ids,
+            fitness: self.fitness,
+            breakdown: self.breakdown,
+            packed_layout: self.packed_layout,
+        }
+    }
+}
+
+/// Distribution of every image of the pool over several collages (pages).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartitionIndividual {
+    /// Page index of each image, parallel to `GaContext::all_ids`.
+    pub assignment: Vec<usize>,
+    pub fitness: f64,
+    /// The evaluated collage of each page.
+    #[serde(skip)]
+    pub pages: Vec<Individual>,
+}
+
+pub fn create_random_partition(num_images: usize, pages: usize, rng: &mut impl Rng) -> PartitionIndividual {
+    // Deal the images round robin before shuffling so no page starts out empty
+    let mut assignment: Vec<usize> = (0..num_images).map(|i| i % pages).collect();
+    assignment.shuffle(rng);
+
+    PartitionIndividual {
+        assignment,
+        fitness: 0.0,
+        pages: vec![],
+    }
+}
+
+/// Moves images from the fullest pages to empty ones until every page has at least one image.
+pub fn repair_partition(assignment: &mut [usize], pages: usize, rng: &mut impl Rng) {
+    for page in 0..pages {
+        let mut counts = vec![0usize; pages];
+        for &p in assignment.iter() {
+            counts[p] += 1;
+        }
+        if counts[page] > 0 {
+            continue;
+        }
+        let fullest = (0..pages).max_by_key(|&p| counts[p]).unwrap();
+        if counts[fullest] < 2 {
+            break;
+        }
+        let candidates: Vec<usize> = (0..assignment.len()).filter(|&i| assignment[i] == fullest).collect();
+        if let Some(&idx) = candidates.choose(rng) {
+            assignment[idx] = page;
+        }
+    }
+}
+
+/// Lays out every page and scores the partition.
+///
+/// The score is the mean page quality weighted by the number of images per page,
+/// times a balance factor that favours pages holding a similar number of images.
+/// The quality of a page is one over its [`FitnessWeights::penalty`]; the reward
+/// terms are left out since every image is used anyway.
+pub fn evaluate_partition(partition: &mut PartitionIndividual, ctx: &GaContext) {
+    partition.pages = (0..ctx.pages)
+        .map(|page| {
+            let image_ids = ctx
+                .all_ids
+                .iter()
+                .zip(&partition.assignment)
+                .filter(|(_, &p)| p == page)
+                .map(|(id, _)| *id)
+                .collect();
+            let mut indiv = Individual {
+                image_ids,
+                scales: HashMap::new(),
+                fitness: 0.0,
+                breakdown: FitnessBreakdown::default(),
+                packed_layout: None,
+            };
+            evaluate_individual(&mut indiv, ctx);
+            indiv
+        })
+        .collect();
+
+    let total_images = partition.assignment.len().max(1) as f64;
+    let max_count = partition.pages.iter().map(|page| page.image_ids.len()).max().unwrap_or(0).max(1) as f64;
+    let weighted_quality: f64 = partition
+        .pages
+        .iter()
+        .filter(|page| page.packed_layout.is_some())
+        .map(|page| page.image_ids.len() as f64 / ctx.fitness.penalty(&page.breakdown))
+        .sum::<f64>()
+        / total_images;
+    let balance = total_images / ctx.pages as f64 / max_count;
+    partition.fitness = weighted_quality * balance;
+}
+
+pub fn crossover_partition(
+    parent1: &PartitionIndividual,
+    parent2: &PartitionIndividual,
+    pages: usize,
+    rng: &mut impl Rng,
+) -> PartitionIndividual {
+    // Uniform crossover: every image keeps the page of one of the parents
+    let mut assignment: Vec<usize> = parent1
+        .assignment
+        .iter()
+        .zip(&parent2.assignment)
+        .map(|(&a, &b)| if rng.gen::<bool>() { a } else { b })
+        .collect();
+    repair_partition(&mut assignment, pages, rng);
+
+    PartitionIndividual {
+        assignment,
+        fitness: 0.0,
+        pages: vec![],
+    }
+}
+
+pub fn mutate_partition(partition: &mut PartitionIndividual, pages: usize, rng: &mut impl Rng) {
+    let n = partition.assignment.len();
+    if n == 0 || pages < 2 {
+        return;
+    }
+
+    if rng.gen::<f64>() < 0.5 {
+        // Move an image to another page
+        let idx = rng.gen_range(0..n);
+        let offset = rng.gen_range(1..pages);
+        partition.assignment[idx] = (partition.assignment[idx] + offset) % pages;
+    } else {
+        // Swap the pages of two images
+        let a = rng.gen_range(0..n);
+        let b = rng.gen_range(0..n);
+        partition.assignment.swap(a, b);
+    }
+
+    repair_partition(&mut partition.assignment, pages, rng);
+}
+
+impl Genome for PartitionIndividual {
+    fn random(ctx: &GaContext, rng: &mut GaRng) -> Self {
+        create_random_partition(ctx.all_ids.len(), ctx.pages, rng)
+    }
+
+    fn crossover(&self, other: &Self, ctx: &GaContext, rng: &mut GaRng) -> Self {
+        crossover_partition(self, other, ctx.pages, rng)
+    }
+
+    fn mutate(&mut self, ctx: &GaContext, rng: &mut GaRng) {
+        mutate_partition(self, ctx.pages, rng)
+    }
+
+    fn repair(&mut self, ctx: &GaContext, rng: &mut GaRng) {
+        repair_partition(&mut self.assignment, ctx.pages, rng)
+    }
+
+    fn evaluate(&mut self, ctx: &GaContext) {
+        evaluate_partition(self, ctx)
+    }
+
+    fn fitness(&self) -> f64 {
+        self.fitness
+    }
+
+    /// The share of images assigned to a different page.
+    fn distance(&self, other: &Self) -> f64 {
+        if self.assignment.is_empty() {
+            return 0.0;
+        }
+        let different = self.assignment.iter().zip(&other.assignment).filter(|(a, b)| a != b).count();
+        different as f64 / self.assignment.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two random permutations of `len` IDs, drawn from `rng`.
+    fn parents(len: usize, rng: &mut GaRng) -> (Vec<u32>, Vec<u32>) {
+        let mut parent1: Vec<u32> = (0..len as u32).map(|id| id * 3 + 10).collect();
+        let mut parent2 = parent1.clone();
+        parent1.shuffle(rng);
+        parent2.shuffle(rng);
+        (parent1, parent2)
+    }
+
+    fn sorted(ids: &[u32]) -> Vec<u32> {
+        let mut ids = ids.to_vec();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn permutation_crossovers_return_permutations() {
+        for len in 1..=8 {
+            for seed in 0..50 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let (parent1, parent2) = parents(len, &mut rng);
+                let children = [
+                    ordered_crossover(&parent1, &parent2, &mut rng),
+                    partially_mapped_crossover(&parent1, &parent2, &mut rng),
+                    cycle_crossover(&parent1, &parent2),
+                ];
+                for child in children {
+                    assert_eq!(sorted(&child), sorted(&parent1), "{:?} x {:?} gave {:?}", parent1, parent2, child);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_and_partially_mapped_crossover_keep_the_slice_of_parent1() {
+        for len in 2..=8 {
+            for seed in 0..50 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let (parent1, parent2) = parents(len, &mut rng);
+                // Both operators draw the slice first
+                let (i, j) = random_slice(len, &mut rng.clone());
+                let ox = ordered_crossover(&parent1, &parent2, &mut rng.clone());
+                let pmx = partially_mapped_crossover(&parent1, &parent2, &mut rng.clone());
+                assert_eq!(ox[i..=j], parent1[i..=j]);
+                assert_eq!(pmx[i..=j], parent1[i..=j]);
+            }
+        }
+    }
+
+    #[test]
+    fn cycle_crossover_takes_every_position_from_a_parent() {
+        for len in 1..=8 {
+            for seed in 0..50 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let (parent1, parent2) = parents(len, &mut rng);
+                let child = cycle_crossover(&parent1, &parent2);
+                for k in 0..len {
+                    assert!(child[k] == parent1[k] || child[k] == parent2[k], "position {} of {:?}", k, child);
+                }
+            }
+        }
+    }
+
+    /// A context over the IDs 0..12 with two locked images and 3 to 7 images per collage.
+    fn tree_context<'a>(images: &'a HashMap<u32, DynamicImage>, layout: &'a LayoutConfig, fitness: &'a FitnessWeights) -> GaContext<'a> {
+        GaContext {
+            images,
+            all_ids: (0..12).collect(),
+            locked: vec![2, 9],
+            min_images: 3,
+            max_images: 7,
+            crossover: CrossoverKind::default(),
+            scale_range: (1.0, 1.0),
+            pages: 1,
+            layout,
+            fitness,
+            colours: HashMap::new(),
+            target_colour: None,
+            near_duplicates: Vec::new(),
+        }
+    }
+
+    fn has_duplicates(ids: &[u32]) -> bool {
+        sorted(ids).windows(2).any(|pair| pair[0] == pair[1])
+    }
+
+    #[test]
+    fn subtree_crossover_and_repair_keep_the_leaves_unique() {
+        let (images, layout, fitness) = (HashMap::new(), LayoutConfig::default(), FitnessWeights::default());
+        let ctx = tree_context(&images, &layout, &fitness);
+        for seed in 0..200 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let tree = SlicingTree::random(&ctx, &mut rng).root;
+            let other = SlicingTree::random(&ctx, &mut rng).root;
+
+            let child = subtree_crossover(&tree, &other, &mut rng);
+            let leaves = child.leaves();
+            assert!(!has_duplicates(&leaves), "{:?}", leaves);
+            let parent_leaves = [tree.leaves(), other.leaves()].concat();
+            assert!(leaves.iter().all(|id| parent_leaves.contains(id)), "{:?}", leaves);
+
+            let repaired = enforce_tree_limits(child, &ctx, &mut rng).leaves();
+            assert!(!has_duplicates(&repaired), "{:?}", repaired);
+            assert!((ctx.min_images..=ctx.max_images).contains(&repaired.len()), "{:?}", repaired);
+            assert!(ctx.locked.iter().all(|id| repaired.contains(id)), "{:?}", repaired);
+            // Images are only cut out when there are too many with the locked ones put back
+            let missing_locked = ctx.locked.iter().filter(|id| !leaves.contains(id)).count();
+            if leaves.len() + missing_locked <= ctx.max_images {
+                assert!(leaves.iter().all(|id| repaired.contains(id)), "{:?} lost images of {:?}", repaired, leaves);
+            }
+        }
+    }
+
+    #[test]
+    fn repair_cuts_surplus_images_but_keeps_locked_ones() {
+        let (images, layout, fitness) = (HashMap::new(), LayoutConfig::default(), FitnessWeights::default());
+        let ctx = tree_context(&images, &layout, &fitness);
+        for seed in 0..50 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let all: Vec<u32> = (0..12).collect();
+            let tree = random_slicing_tree(&all, &mut rng).unwrap();
+            let repaired = enforce_tree_limits(tree, &ctx, &mut rng).leaves();
+            assert_eq!(repaired.len(), ctx.max_images);
+            assert!(!has_duplicates(&repaired));
+            assert!(ctx.locked.iter().all(|id| repaired.contains(id)), "{:?}", repaired);
+        }
+    }
+
+    #[test]
+    fn mutate_order_keeps_the_images() {
+        for len in 1..=8 {
+            for seed in 0..50 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let (image_ids, _) = parents(len, &mut rng);
+                let mut indiv = Individual {
+                    image_ids: image_ids.clone(),
+                    scales: HashMap::new(),
+                    fitness: 0.0,
+                    breakdown: FitnessBreakdown::default(),
+                    packed_layout: None,
+                };
+                mutate_order(&mut indiv, &mut rng);
+                assert_eq!(sorted(&indiv.image_ids), sorted(&image_ids));
+            }
+        }
+    }
 }
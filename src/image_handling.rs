@@ -1,29 +1,54 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use glob::Pattern;
 use image::imageops::{resize, FilterType};
-use image::{DynamicImage, GenericImageView};
+use image::{DynamicImage, GenericImageView, GrayImage, ImageResult};
 
-pub fn load_images(dir: &str, filter: Option<String>, standard_width: Option<u32>) -> Vec<(u32, DynamicImage)> {
+use crate::packing::Crop;
+
+/// Where a loaded image came from and its size before scaling.
+#[derive(Clone, Debug)]
+pub struct ImageInfo {
+    pub path: PathBuf,
+    pub original_width: u32,
+    pub original_height: u32,
+    /// [`perceptual_hash`] of the image.
+    pub hash: u64,
+}
+
+/// Loads the images of `dir` and returns them keyed by ID, together with the
+/// source information of every loaded image.
+pub fn load_images(
+    dir: &str,
+    filter: Option<String>,
+    standard_width: Option<u32>,
+) -> (Vec<(u32, DynamicImage)>, HashMap<u32, ImageInfo>) {
     println!("Loading images from directory: {}", dir);
     let entries = match fs::read_dir(dir) {
         Ok(entries) => entries,
         Err(e) => {
             eprintln!("Error reading directory {}: {}", dir, e);
-            return Vec::new();
+            return (Vec::new(), HashMap::new());
         }
     };
 
+    // Sort the entries so image IDs do not depend on the directory iteration order
+    let mut paths = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(e) => paths.push(e.path()),
+            Err(e) => eprintln!("Error reading an entry: {}", e),
+        }
+    }
+    paths.sort();
+
     let mut images = Vec::new();
+    let mut infos = HashMap::new();
     let mut id_counter = 0;
 
-    for entry in entries {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("Error reading an entry: {}", e);
-                continue;
-            }
-        };
-        let path = entry.path();
+    for path in paths {
         let passes_filter = if let Some(f) = &filter {
             if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
                 name.contains(f)
@@ -36,12 +61,11 @@ pub fn load_images(dir: &str, filter: Option<String>, standard_width: Option<u32
 
         if path.is_file() && passes_filter {
             println!("Opening image: {}", path.display());
-            let img_result = image::open(&path);
-            match img_result {
-                Ok(img) => {
+            match load_image(&path, standard_width) {
+                Ok((scaled_img, info)) => {
                     println!("Successfully opened: {}", path.display());
-                    let scaled_img = scale_to_standard_width(&img, standard_width);
                     images.push((id_counter, scaled_img));
+                    infos.insert(id_counter, info);
                     id_counter += 1;
                 }
                 Err(e) => {
@@ -55,7 +79,293 @@ pub fn load_images(dir: &str, filter: Option<String>, standard_width: Option<u32
     }
 
     println!("Total images loaded: {}", images.len());
-    images
+    (images, infos)
+}
+
+/// IDs of the images whose file name or path matches the glob `pattern`, sorted ascending.
+pub fn matching_images(infos: &HashMap<u32, ImageInfo>, pattern: &str) -> Result<Vec<u32>, String> {
+    let pattern = Pattern::new(pattern).map_err(|e| format!("Invalid pattern {}: {}", pattern, e))?;
+    let mut ids: Vec<u32> = infos
+        .iter()
+        .filter(|(_, info)| {
+            let name_matches = info
+                .path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| pattern.matches(name));
+            name_matches || pattern.matches_path(&info.path)
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+/// Fingerprint of a set of loaded images: a 64-bit FNV-1a hash over the IDs,
+/// sizes and pixels in ID order. It changes with the directory contents as well
+/// as with the options the images were loaded with.
+pub fn image_set_hash(images: &HashMap<u32, DynamicImage>) -> u64 {
+    const PRIME: u64 = 0x100_0000_01b3;
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash = (hash ^ b as u64).wrapping_mul(PRIME);
+        }
+    };
+
+    let mut ids: Vec<&u32> = images.keys().collect();
+    ids.sort();
+    for id in ids {
+        let img = &images[id];
+        feed(&id.to_le_bytes());
+        feed(&img.width().to_le_bytes());
+        feed(&img.height().to_le_bytes());
+        feed(img.as_bytes());
+    }
+    hash
+}
+
+/// Difference hash (dHash) of `img`: the image is shrunk to 9x8 grey pixels and
+/// every bit tells whether a pixel is brighter than its right neighbour. Resized,
+/// recompressed or slightly edited copies of a photo get hashes that differ in
+/// few bits, see [`hamming_distance`].
+pub fn perceptual_hash(img: &DynamicImage) -> u64 {
+    let small = img.thumbnail_exact(9, 8).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+/// Number of bits in which two perceptual hashes differ, from 0 (alike) to 64.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Pairs of images whose hashes differ in at most `threshold` bits, as (lower ID,
+/// higher ID) in ascending order.
+pub fn near_duplicate_pairs(infos: &HashMap<u32, ImageInfo>, threshold: u32) -> Vec<(u32, u32)> {
+    let mut ids: Vec<u32> = infos.keys().copied().collect();
+    ids.sort();
+    let mut pairs = Vec::new();
+    for (i, &a) in ids.iter().enumerate() {
+        for &b in &ids[i + 1..] {
+            if hamming_distance(infos[&a].hash, infos[&b].hash) <= threshold {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+/// The images of `ids` to leave out so that no near-duplicates remain: going
+/// through `ids` in order, an image is dropped if its hash is within `threshold`
+/// bits of one that was kept or of one of `keep`, which are never dropped.
+/// IDs without [`ImageInfo`] are kept.
+pub fn redundant_images(ids: &[u32], keep: &[u32], infos: &HashMap<u32, ImageInfo>, threshold: u32) -> Vec<u32> {
+    let mut kept: Vec<u64> = keep.iter().filter_map(|id| infos.get(id)).map(|info| info.hash).collect();
+    let mut dropped = Vec::new();
+    for &id in ids.iter().filter(|id| !keep.contains(id)) {
+        let Some(info) = infos.get(&id) else {
+            continue;
+        };
+        if kept.iter().any(|&hash| hamming_distance(hash, info.hash) <= threshold) {
+            dropped.push(id);
+        } else {
+            kept.push(info.hash);
+        }
+    }
+    dropped
+}
+
+/// What `--dedupe` does with near-duplicate images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupeMode {
+    /// Keep only one image of every group of near-duplicates.
+    #[default]
+    Drop,
+    /// Keep all images but penalise collages that show near-duplicates together.
+    Penalise,
+}
+
+impl FromStr for DedupeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "drop" => Ok(DedupeMode::Drop),
+            "penalise" | "penalize" => Ok(DedupeMode::Penalise),
+            _ => Err(format!("Unknown dedupe mode: {}", s)),
+        }
+    }
+}
+
+/// Number of bins of [`ColourStats::luminance_histogram`].
+pub const LUMINANCE_BINS: usize = 16;
+
+/// Colour difference (CIE76 ΔE) below which two images count as similar in colour.
+pub const SIMILAR_COLOUR_DELTA_E: f64 = 15.0;
+
+/// Colour properties of an image that fitness terms compare between images.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColourStats {
+    /// Mean colour of the pixels in CIE L*a*b*.
+    pub mean_lab: [f64; 3],
+    /// Share of the pixels in each of [`LUMINANCE_BINS`] equal ranges of L* from 0 to 100.
+    pub luminance_histogram: [f64; LUMINANCE_BINS],
+    /// Centre in degrees of the 10° range that holds most of the hues, every pixel
+    /// weighted by its saturation.
+    pub dominant_hue: f64,
+    /// Mean HSV saturation of the pixels; the hue of a grey image means nothing.
+    pub saturation: f64,
+}
+
+impl ColourStats {
+    /// How alike two images look in colour, from 0 to 1: the closeness of their
+    /// mean colours (0 from [`SIMILAR_COLOUR_DELTA_E`] on) times the overlap of
+    /// their luminance histograms.
+    pub fn similarity(&self, other: &ColourStats) -> f64 {
+        let closeness = (1.0 - delta_e(self.mean_lab, other.mean_lab) / SIMILAR_COLOUR_DELTA_E).max(0.0);
+        let overlap: f64 = self
+            .luminance_histogram
+            .iter()
+            .zip(&other.luminance_histogram)
+            .map(|(a, b)| a.min(*b))
+            .sum();
+        closeness * overlap
+    }
+}
+
+/// Computes the colour statistics of `img` from a thumbnail.
+pub fn colour_stats(img: &DynamicImage) -> ColourStats {
+    const HUE_BINS: usize = 36;
+    let small = img.thumbnail(64, 64).to_rgb8();
+    let count = (small.width() * small.height()).max(1) as f64;
+    let mut lab_sum = [0.0; 3];
+    let mut luminance_histogram = [0.0; LUMINANCE_BINS];
+    let mut hue_histogram = [0.0; HUE_BINS];
+    let mut saturation_sum = 0.0;
+    for pixel in small.pixels() {
+        let lab = srgb_to_lab(pixel.0);
+        for (s, v) in lab_sum.iter_mut().zip(lab) {
+            *s += v;
+        }
+        let bin = ((lab[0] / 100.0 * LUMINANCE_BINS as f64) as usize).min(LUMINANCE_BINS - 1);
+        luminance_histogram[bin] += 1.0 / count;
+
+        let (hue, saturation) = hue_saturation(pixel.0.map(|v| v as f64 / 255.0));
+        hue_histogram[(hue / 360.0 * HUE_BINS as f64) as usize % HUE_BINS] += saturation;
+        saturation_sum += saturation;
+    }
+    let dominant_bin = (0..HUE_BINS)
+        .max_by(|&a, &b| hue_histogram[a].partial_cmp(&hue_histogram[b]).unwrap())
+        .unwrap_or(0);
+    ColourStats {
+        mean_lab: lab_sum.map(|s| s / count),
+        luminance_histogram,
+        dominant_hue: (dominant_bin as f64 + 0.5) * 360.0 / HUE_BINS as f64,
+        saturation: saturation_sum / count,
+    }
+}
+
+/// Converts an sRGB colour to CIE L*a*b* with the D65 white point.
+pub fn srgb_to_lab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|v| {
+        let c = v as f64 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// The CIE76 colour difference ΔE: the distance of two L*a*b* colours.
+pub fn delta_e(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(&b).map(|(x, y)| (x - y).powi(2)).sum::<f64>().sqrt()
+}
+
+/// Hue in degrees and HSV saturation of an RGB colour with components between 0 and 1.
+fn hue_saturation([r, g, b]: [f64; 3]) -> (f64, f64) {
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let sector = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    (sector * 60.0, delta / max)
+}
+
+/// Moves `crop` to the window of the same size with the most detail, measured as
+/// the entropy of the luminance histogram. Ties keep the window closest to the centre.
+pub fn entropy_crop(img: &DynamicImage, crop: Crop) -> Crop {
+    const STEPS: u32 = 16;
+    let small = img.thumbnail(128, 128).to_luma8();
+    let mut best: Option<(f64, f64, Crop)> = None;
+    for step in 0..=STEPS {
+        let t = step as f64 / STEPS as f64;
+        let candidate = Crop {
+            x: (1.0 - crop.width) * t,
+            y: (1.0 - crop.height) * t,
+            ..crop
+        };
+        let entropy = window_entropy(&small, &candidate);
+        let off_center = (t - 0.5).abs();
+        if best.is_none_or(|(e, d, _)| entropy > e || (entropy == e && off_center < d)) {
+            best = Some((entropy, off_center, candidate));
+        }
+    }
+    best.map_or(crop, |(_, _, c)| c)
+}
+
+/// Shannon entropy in bits of the grey values inside `crop`.
+fn window_entropy(img: &GrayImage, crop: &Crop) -> f64 {
+    let (w, h) = img.dimensions();
+    let x0 = ((crop.x * w as f64).round() as u32).min(w - 1);
+    let y0 = ((crop.y * h as f64).round() as u32).min(h - 1);
+    let x1 = (((crop.x + crop.width) * w as f64).round() as u32).clamp(x0 + 1, w);
+    let y1 = (((crop.y + crop.height) * h as f64).round() as u32).clamp(y0 + 1, h);
+
+    let mut histogram = [0u32; 256];
+    for y in y0..y1 {
+        for x in x0..x1 {
+            histogram[img.get_pixel(x, y)[0] as usize] += 1;
+        }
+    }
+    let total = ((x1 - x0) * (y1 - y0)) as f64;
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Opens a single image and scales it to `standard_width` if given.
+pub fn load_image(path: &Path, standard_width: Option<u32>) -> ImageResult<(DynamicImage, ImageInfo)> {
+    let img = image::open(path)?;
+    let (original_width, original_height) = img.dimensions();
+    let info = ImageInfo {
+        path: path.to_path_buf(),
+        original_width,
+        original_height,
+        hash: perceptual_hash(&img),
+    };
+    Ok((scale_to_standard_width(&img, standard_width), info))
 }
 
 fn scale_to_standard_width(
@@ -72,3 +382,118 @@ fn scale_to_standard_width(
         img.to_rgba8().into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageOutputFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    const THRESHOLD: u32 = 10;
+
+    fn assert_lab(actual: [f64; 3], expected: [f64; 3]) {
+        assert!(delta_e(actual, expected) < 0.05, "{:?} is not {:?}", actual, expected);
+    }
+
+    fn solid(rgb: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(32, 24, Rgb(rgb)))
+    }
+
+    /// A smooth test picture whose pattern depends on `frequency`.
+    fn picture(frequency: f64) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(240, 160, |x, y| {
+            let v = 128.0 + 100.0 * (x as f64 * frequency / 240.0).sin() * (y as f64 * 3.0 / 160.0).cos();
+            Rgb([v as u8, (v * 0.8) as u8, (255.0 - v) as u8])
+        }))
+    }
+
+    fn reencoded(img: &DynamicImage) -> DynamicImage {
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Jpeg(60)).unwrap();
+        image::load_from_memory(&bytes).unwrap()
+    }
+
+    fn info(img: &DynamicImage) -> ImageInfo {
+        ImageInfo {
+            path: PathBuf::new(),
+            original_width: img.width(),
+            original_height: img.height(),
+            hash: perceptual_hash(img),
+        }
+    }
+
+    /// Images 0 and 2 are copies of one picture, 1 and 3 of another.
+    fn infos() -> HashMap<u32, ImageInfo> {
+        let (a, b) = (picture(7.0), picture(13.0));
+        HashMap::from([
+            (0, info(&a)),
+            (1, info(&b)),
+            (2, info(&reencoded(&a))),
+            (3, info(&b.resize_exact(120, 80, FilterType::Triangle))),
+        ])
+    }
+
+    #[test]
+    fn srgb_to_lab_matches_reference_values() {
+        assert_lab(srgb_to_lab([255, 255, 255]), [100.0, 0.0, 0.0]);
+        assert_lab(srgb_to_lab([0, 0, 0]), [0.0, 0.0, 0.0]);
+        assert_lab(srgb_to_lab([128, 128, 128]), [53.585, 0.0, 0.0]);
+        assert_lab(srgb_to_lab([255, 0, 0]), [53.24, 80.09, 67.20]);
+    }
+
+    #[test]
+    fn delta_e_is_the_lab_distance() {
+        assert_eq!(delta_e([50.0, 10.0, -5.0], [50.0, 10.0, -5.0]), 0.0);
+        assert!((delta_e([50.0, 0.0, 0.0], [53.0, 4.0, 0.0]) - 5.0).abs() < 1e-12);
+        let black_to_white = delta_e(srgb_to_lab([0, 0, 0]), srgb_to_lab([255, 255, 255]));
+        assert!((black_to_white - 100.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn colour_stats_of_solid_images() {
+        let red = colour_stats(&solid([255, 0, 0]));
+        assert_lab(red.mean_lab, srgb_to_lab([255, 0, 0]));
+        assert!((red.saturation - 1.0).abs() < 1e-12);
+        assert_eq!(red.dominant_hue, 5.0);
+
+        let grey = colour_stats(&solid([128, 128, 128]));
+        assert_eq!(grey.saturation, 0.0);
+        // L* of 53.6 falls into the ninth of 16 bins
+        assert!((grey.luminance_histogram[8] - 1.0).abs() < 1e-9);
+
+        assert!((red.similarity(&red) - 1.0).abs() < 1e-12);
+        assert_eq!(red.similarity(&grey), 0.0);
+    }
+
+    #[test]
+    fn copies_hash_alike_and_other_pictures_do_not() {
+        let a = picture(7.0);
+        let hash = perceptual_hash(&a);
+        assert_eq!(hamming_distance(hash, perceptual_hash(&a.clone())), 0);
+        assert!(hamming_distance(hash, perceptual_hash(&reencoded(&a))) <= THRESHOLD);
+        assert!(hamming_distance(hash, perceptual_hash(&a.resize_exact(120, 80, FilterType::Triangle))) <= THRESHOLD);
+        assert!(hamming_distance(hash, perceptual_hash(&picture(13.0))) > THRESHOLD);
+        assert!(hamming_distance(hash, perceptual_hash(&a.fliph())) > THRESHOLD);
+    }
+
+    #[test]
+    fn near_duplicate_pairs_finds_the_copies() {
+        assert_eq!(near_duplicate_pairs(&infos(), THRESHOLD), vec![(0, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn redundant_images_keeps_one_image_of_each_pair() {
+        let infos = infos();
+        assert_eq!(redundant_images(&[0, 1, 2, 3], &[], &infos, THRESHOLD), vec![2, 3]);
+        assert_eq!(redundant_images(&[3, 2, 1, 0], &[], &infos, THRESHOLD), vec![1, 0]);
+    }
+
+    #[test]
+    fn redundant_images_never_drops_kept_images() {
+        let infos = infos();
+        assert_eq!(redundant_images(&[0, 1, 2, 3], &[2], &infos, THRESHOLD), vec![0, 3]);
+        assert_eq!(redundant_images(&[0, 1, 2, 3], &[0, 2], &infos, THRESHOLD), vec![3]);
+        // Images without a hash are left alone
+        assert_eq!(redundant_images(&[0, 7, 2], &[], &infos, THRESHOLD), vec![2]);
+    }
+}
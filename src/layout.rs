@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, GenericImageView, Rgba};
+use rect_packer::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::collage::{centering_offset, create_collage};
+use crate::ga::{FitnessBreakdown, Individual};
+use crate::image_handling::{load_image, ImageInfo};
+use crate::packing::{
+    fit_to_canvas, pack_sizes, slicing_layout, slicing_tree, Crop, LayoutConfig, LayoutMode, PackerKind, Placement,
+};
+use crate::output::create_output_file;
+
+/// Serializable description of an optimized collage, enough to re-render or
+/// hyperlink it without rerunning the GA.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutFile {
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    /// Shift applied to every packed position to center the images on the canvas.
+    pub offset_x: i64,
+    pub offset_y: i64,
+    /// Spacing between images, outer margin and RGBA background the layout was made with.
+    pub padding: u32,
+    pub margin: u32,
+    pub background: [u8; 4],
+    /// Heuristic and arrangement used when the placements have to be re-packed.
+    pub packer: PackerKind,
+    pub mode: LayoutMode,
+    pub fitness: f64,
+    pub breakdown: FitnessBreakdown,
+    pub placements: Vec<PlacementRecord>,
+}
+
+/// One image of a [`LayoutFile`]. `x`/`y` are packed coordinates; add the layout
+/// offset to get the position on the canvas.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlacementRecord {
+    pub id: u32,
+    /// Source file, absent for images that were not loaded from disk.
+    pub path: Option<PathBuf>,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub scaled_width: u32,
+    pub scaled_height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// The image is drawn turned 90° clockwise; `width` and `height` are the rotated size.
+    pub rotated: bool,
+    /// Drawn part of the (turned) image in fractions of its size; the whole image if absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<Crop>,
+}
+
+/// Builds the layout description of an evaluated individual.
+///
+/// Returns `None` if the individual has no packed layout.
+pub fn build_layout(
+    indiv: &Individual,
+    images: &HashMap<u32, DynamicImage>,
+    infos: &HashMap<u32, ImageInfo>,
+    config: &LayoutConfig,
+) -> Option<LayoutFile> {
+    let (packed_locations, w, h) = indiv.packed_layout.as_ref()?;
+    let (offset_x, offset_y) = centering_offset(packed_locations, *w, *h);
+
+    let placements = packed_locations
+        .iter()
+        .map(|Placement { id, rect, rotated, crop }| {
+            let (scaled_width, scaled_height) = images.get(id).map(|img| img.dimensions()).unwrap_or((0, 0));
+            let info = infos.get(id);
+            PlacementRecord {
+                id: *id,
+                // Absolute paths keep the layout usable from another working directory
+                path: info.map(|i| fs::canonicalize(&i.path).unwrap_or_else(|_| i.path.clone())),
+                original_width: info.map_or(scaled_width, |i| i.original_width),
+                original_height: info.map_or(scaled_height, |i| i.original_height),
+                scaled_width,
+                scaled_height,
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+                rotated: *rotated,
+                crop: *crop,
+            }
+        })
+        .collect();
+
+    Some(LayoutFile {
+        canvas_width: *w,
+        canvas_height: *h,
+        offset_x,
+        offset_y,
+        padding: config.padding,
+        margin: config.margin,
+        background: config.background,
+        packer: config.packer,
+        mode: config.mode,
+        fitness: indiv.fitness,
+        breakdown: indiv.breakdown.clone(),
+        placements,
+    })
+}
+
+/// Writes `layout` as pretty-printed JSON, refusing to replace an existing file unless `force` is set.
+pub fn write_layout_json(layout: &LayoutFile, path: &Path, force: bool) -> Result<(), String> {
+    let file = create_output_file(path, force)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), layout)
+        .map_err(|e| format!("Error writing layout {}: {}", path.display(), e))
+}
+
+/// Reads a layout written by [`write_layout_json`].
+pub fn read_layout_json(path: &Path) -> Result<LayoutFile, String> {
+    let file = File::open(path).map_err(|e| format!("Error opening layout {}: {}", path.display(), e))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Error reading layout {}: {}", path.display(), e))
+}
+
+/// How a saved layout is rendered again.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Factor applied to the canvas and every placement.
+    pub scale: f64,
+    /// Exact output size; the layout is scaled to fit and centered. Overrides `scale`.
+    pub canvas: Option<(u32, u32)>,
+    /// New spacing between images. The placements are re-packed in their saved order.
+    pub padding: Option<u32>,
+    /// New outer margin. The placements are re-packed in their saved order.
+    pub margin: Option<u32>,
+    /// New RGBA background colour.
+    pub background: Option<[u8; 4]>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            scale: 1.0,
+            canvas: None,
+            padding: None,
+            margin: None,
+            background: None,
+        }
+    }
+}
+
+/// Largest width or height of a rendered collage, the limit of the JPEG format.
+pub const MAX_CANVAS_SIDE: u32 = 65_535;
+
+/// Checks that the canvas of `layout` has a usable size and that every placement
+/// has a positive size and lies on it after the layout offset is applied.
+fn validate_layout(layout: &LayoutFile) -> Result<(), String> {
+    let (canvas_width, canvas_height) = (layout.canvas_width, layout.canvas_height);
+    if canvas_width == 0 || canvas_height == 0 || canvas_width > MAX_CANVAS_SIDE || canvas_height > MAX_CANVAS_SIDE {
+        return Err(format!("Invalid canvas size in the layout: {}x{}", canvas_width, canvas_height));
+    }
+    for placement in &layout.placements {
+        if placement.width <= 0 || placement.height <= 0 {
+            return Err(format!("Image {} has an invalid size of {}x{}", placement.id, placement.width, placement.height));
+        }
+        let x = placement.x as i64 + layout.offset_x;
+        let y = placement.y as i64 + layout.offset_y;
+        if x < 0 || y < 0 || x + placement.width as i64 > canvas_width as i64 || y + placement.height as i64 > canvas_height as i64 {
+            return Err(format!(
+                "Image {} at ({}, {}) with size {}x{} does not fit the {}x{} canvas",
+                placement.id, x, y, placement.width, placement.height, canvas_width, canvas_height
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Reloads the source images of `layout` and renders it again.
+///
+/// Fails if a placement does not lie on the canvas, if a source file no longer
+/// exists or if its dimensions differ from the ones recorded when the layout
+/// was optimized.
+pub fn render_layout(layout: &LayoutFile, options: &RenderOptions) -> Result<DynamicImage, String> {
+    if options.scale <= 0.0 || !options.scale.is_finite() {
+        return Err(format!("Invalid render scale: {}", options.scale));
+    }
+    validate_layout(layout)?;
+
+    // The originals are kept at full resolution; create_collage resizes them to their placement
+    let mut images = HashMap::new();
+    let mut packed_locations = Vec::new();
+    for placement in &layout.placements {
+        let path = placement
+            .path
+            .as_ref()
+            .ok_or_else(|| format!("Image {} has no source path in the layout", placement.id))?;
+        if !path.is_file() {
+            return Err(format!("Source image {} does not exist", path.display()));
+        }
+        let (img, info) = load_image(path, None).map_err(|e| format!("Error opening {}: {}", path.display(), e))?;
+        if (info.original_width, info.original_height) != (placement.original_width, placement.original_height) {
+            return Err(format!(
+                "Source image {} is {}x{} but the layout expects {}x{}",
+                path.display(),
+                info.original_width,
+                info.original_height,
+                placement.original_width,
+                placement.original_height
+            ));
+        }
+
+        images.insert(placement.id, img);
+        let rect = Rect {
+            x: placement.x,
+            y: placement.y,
+            width: placement.width,
+            height: placement.height,
+        };
+        packed_locations.push(Placement {
+            id: placement.id,
+            rect,
+            rotated: placement.rotated,
+            crop: placement.crop,
+        });
+    }
+
+    let mut packed = (packed_locations, layout.canvas_width, layout.canvas_height);
+    let margin = options.margin.unwrap_or(layout.margin);
+    if options.padding.is_some() || options.margin.is_some() {
+        // Different spacing moves the images, so pack them again in their saved order
+        let config = LayoutConfig {
+            aspect_ratio: layout.canvas_width as f64 / layout.canvas_height.max(1) as f64,
+            padding: options.padding.unwrap_or(layout.padding),
+            margin,
+            packer: layout.packer,
+            mode: layout.mode,
+            ..LayoutConfig::default()
+        };
+        let sizes: Vec<(u32, u32, u32)> = match layout.mode {
+            LayoutMode::Packed => packed
+                .0
+                .iter()
+                .map(|p| (p.id, p.rect.width as u32, p.rect.height as u32))
+                .collect(),
+            // The arrangement is derived from the image sizes, so lay out the uncropped images again
+            _ => layout
+                .placements
+                .iter()
+                .map(|p| (p.id, p.scaled_width, p.scaled_height))
+                .collect(),
+        };
+        let repacked = match layout.mode {
+            // The cuts are not in the file but follow from the saved placements
+            LayoutMode::Slicing => match slicing_tree(&packed.0) {
+                Some(tree) => {
+                    let sizes: HashMap<u32, (u32, u32)> = sizes.iter().map(|&(id, w, h)| (id, (w, h))).collect();
+                    slicing_layout(&tree, &sizes, &config)
+                }
+                None => (vec![], 0, 0),
+            },
+            _ => pack_sizes(&sizes, &config),
+        };
+        if repacked.0.is_empty() {
+            return Err("The layout could not be re-packed with the new spacing.".to_string());
+        }
+        // The rotated sizes are packed as they are, so every image keeps its orientation
+        let placements = match layout.mode {
+            LayoutMode::Packed => repacked
+                .0
+                .into_iter()
+                .zip(&packed.0)
+                .map(|(new, old)| Placement { rotated: old.rotated, crop: old.crop, ..new })
+                .collect(),
+            // Keep every crop centred where it was, e.g. on the detail an entropy crop found
+            _ => repacked
+                .0
+                .into_iter()
+                .zip(&packed.0)
+                .map(|(new, old)| match (new.crop, old.crop) {
+                    (Some(crop), Some(old_crop)) => Placement {
+                        crop: Some(crop.centered_at(
+                            old_crop.x + old_crop.width / 2.0,
+                            old_crop.y + old_crop.height / 2.0,
+                        )),
+                        ..new
+                    },
+                    _ => new,
+                })
+                .collect(),
+        };
+        packed = (placements, repacked.1, repacked.2);
+    }
+
+    let (canvas_width, canvas_height) = match options.canvas {
+        Some(canvas) => canvas,
+        None => {
+            // Only the part inside the margin is scaled; the margin keeps its pixel size
+            let scale_inner = |v: u32| (v.saturating_sub(2 * margin) as f64 * options.scale).round().max(1.0) as u32 + 2 * margin;
+            (scale_inner(packed.1), scale_inner(packed.2))
+        }
+    };
+    if canvas_width == 0 || canvas_height == 0 || canvas_width > MAX_CANVAS_SIDE || canvas_height > MAX_CANVAS_SIDE {
+        return Err(format!("Invalid canvas size: {}x{}", canvas_width, canvas_height));
+    }
+
+    let (packed_locations, w, h) = fit_to_canvas(packed, canvas_width, canvas_height, margin);
+    let background = Rgba(options.background.unwrap_or(layout.background));
+    create_collage(&images, &packed_locations, w, h, background)
+}
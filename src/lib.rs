@@ -0,0 +1,28 @@
+//! Genetic-algorithm based collage optimizer.
+//!
+//! The [`CollageOptimizer`] builder is the main entry point: hand it the loaded
+//! images, tune the GA parameters and call [`CollageOptimizer::run`] to get the
+//! best [`Individual`] and its rendered collage.
+
+pub mod checkpoint;
+pub mod collage;
+pub mod evolution;
+pub mod fitness;
+pub mod ga;
+pub mod image_handling;
+pub mod layout;
+pub mod optimizer;
+pub mod output;
+pub mod packing;
+pub mod pareto;
+
+pub use crate::checkpoint::{checkpoint_info, read_checkpoint, write_checkpoint, Checkpoint, CheckpointInfo};
+pub use crate::evolution::{Elitism, Selection};
+pub use crate::fitness::{read_fitness_weights, FitnessTerm, FitnessWeights};
+pub use crate::ga::{CollageGenome, CrossoverKind, Individual, PartitionIndividual, SlicingTree};
+pub use crate::image_handling::{load_images, matching_images, near_duplicate_pairs, redundant_images, DedupeMode, ImageInfo};
+pub use crate::layout::{build_layout, read_layout_json, render_layout, write_layout_json, LayoutFile, RenderOptions};
+pub use crate::optimizer::{CollageOptimizer, CollageResult, ParetoResult, PartitionResult};
+pub use crate::output::{save_collage, OutputFormat, OutputOptions};
+pub use crate::packing::{Crop, LayoutConfig, LayoutMode, PackerKind, Placement};
+pub use crate::pareto::Tradeoff;
@@ -1,107 +1,544 @@
+mod cli;
+
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-mod cli;
-mod image_handling;
-mod ga;
-mod packing;
-mod collage;
-
-use crate::cli::parse_args;
-use crate::image_handling::load_images;
-use crate::ga::{create_random_individual, evaluate_individual, crossover, mutate, enforce_image_limits, Individual};
-use crate::collage::create_collage;
-use crate::packing::DESIRED_ASPECT_RATIO;
-use rand::seq::SliceRandom;
-use rand::Rng;
-use rayon::prelude::*;
+use image_grid_optimizer::evolution::EvolutionState;
+use image_grid_optimizer::{
+    build_layout, checkpoint_info, load_images, matching_images, near_duplicate_pairs, read_checkpoint, read_fitness_weights, read_layout_json,
+    redundant_images, render_layout, save_collage, write_layout_json, CollageGenome, CollageOptimizer, DedupeMode, FitnessTerm, FitnessWeights,
+    ImageInfo, Individual, LayoutMode, OutputFormat, OutputOptions, PartitionIndividual, Placement, SlicingTree, Tradeoff,
+};
+use serde::de::DeserializeOwned;
+
+use crate::cli::{parse_args, Args, Command, RenderArgs};
 
 fn main() {
-    let (dir, filter, standard_width, population_size, generations, min_images, max_images, mutation_rate, crossover_rate) = parse_args();
+    match parse_args() {
+        Command::Optimize(args) => optimize(*args),
+        Command::Render(args) => render(args),
+    }
+}
+
+/// Checks the output settings up front so a bad path fails before any work is done.
+fn check_output(output_path: &Path, options: &OutputOptions) -> Result<(), String> {
+    if output_path.exists() && !options.force {
+        return Err(format!("{} already exists; use --force to overwrite it", output_path.display()));
+    }
+    if options.format.is_none() && OutputFormat::from_path(output_path).is_none() {
+        return Err(format!("Cannot infer the output format of {}; pass --format", output_path.display()));
+    }
+    Ok(())
+}
+
+/// Warns if a transparent background is written to a format without alpha channel.
+fn warn_dropped_alpha(output_path: &Path, options: &OutputOptions, background: [u8; 4]) {
+    let format = options.format.or_else(|| OutputFormat::from_path(output_path));
+    if background[3] < 255 && format.is_some_and(|f| !f.supports_alpha()) {
+        eprintln!(
+            "Warning: {} does not support transparency; the background will be opaque. Use PNG, WebP or TIFF.",
+            output_path.display()
+        );
+    }
+}
+
+fn optimize(args: Args) {
     println!("Parameters:");
-    println!("Directory: {}", dir);
-    println!("Filter: {:?}", filter);
-    println!("Standard width: {:?}", standard_width);
-    println!("Population size: {}", population_size);
-    println!("Generations: {}", generations);
-    println!("min_images: {}", min_images);
-    println!("max_images: {}", max_images);
-    println!("Mutation rate: {}", mutation_rate);
-    println!("Crossover rate: {}", crossover_rate);
-    println!("Desired aspect ratio: {}", DESIRED_ASPECT_RATIO);
+    println!("Directory: {}", args.dir);
+    println!("Filter: {:?}", args.filter);
+    println!("Standard width: {:?}", args.standard_width);
+    println!("Population size: {}", args.population_size);
+    println!("Generations: {}", args.generations);
+    println!("min_images: {}", args.min_images);
+    println!("max_images: {}", args.max_images);
+    println!("Scale range: {} to {}", args.scale_min, args.scale_max);
+    println!("Mutation rate: {}", args.mutation_rate);
+    println!("Crossover rate: {}", args.crossover_rate);
+    println!("Crossover: {:?}", args.crossover);
+    println!("Selection: {:?}", args.selection);
+    println!("Elitism: {:?}", args.elitism);
+    println!("Stop criteria: {:?}", args.stop);
+    println!("Fitness config: {:?}", args.fitness_config);
+    println!("Weights: {:?}", args.weights);
+    println!("Target colour: {:?}", args.target_colour);
+    println!("Seed: {:?}", args.seed);
+    println!("Pages: {:?}", args.pages);
+    println!("Pareto: {}", args.pareto);
+    println!("Include: {:?}", args.include);
+    println!("Exclude: {:?}", args.exclude);
+    println!("Dedupe: {:?} ({:?})", args.dedupe, args.dedupe_mode);
+    println!("Output: {:?}", args.output);
+    println!("Layout JSON: {:?}", args.layout_json);
+    println!("Desired aspect ratio: {}", args.layout.target_aspect_ratio());
+    println!("Canvas: {:?}", args.layout.canvas);
+    println!("Fill canvas: {}", args.layout.fill_canvas);
+    println!("Allow rotation: {}", args.layout.allow_rotation);
+    println!("Packer: {}", args.layout.packer);
+    println!("Layout mode: {:?}", args.layout.mode);
+    println!("Padding: {}", args.layout.padding);
+    println!("Margin: {}", args.layout.margin);
+    println!("Background: {:?}", args.layout.background);
+    println!("Checkpoint: {:?} (every {} generations)", args.checkpoint, args.checkpoint_every);
+    println!("Resume: {:?}", args.resume);
 
+    if let Some(path) = &args.checkpoint {
+        if Path::new(path).exists() && !args.output_options.force && args.resume.as_ref() != Some(path) {
+            eprintln!("{} already exists; use --force to overwrite it", path);
+            return;
+        }
+    }
+
+    // A resumed run continues as the kind of run it was, whatever the options say
+    let (pages, mode, pareto) = match &args.resume {
+        Some(path) => match checkpoint_info(Path::new(path)) {
+            Ok(info) => (info.pages, info.mode, info.pareto),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        },
+        None => (args.pages, args.layout.mode, args.pareto),
+    };
+    match (pages, mode, pareto) {
+        (Some(pages), _, _) => optimize_pages(&args, pages),
+        (None, LayoutMode::Slicing, true) => optimize_pareto::<SlicingTree>(&args),
+        (None, LayoutMode::Slicing, false) => optimize_single::<SlicingTree>(&args),
+        (None, _, true) => optimize_pareto::<Individual>(&args),
+        (None, _, false) => optimize_single::<Individual>(&args),
+    }
+}
+
+/// The optimizer, the infos of its images and, with `--resume`, the state to continue from.
+type Setup<G> = (CollageOptimizer, HashMap<u32, ImageInfo>, Option<EvolutionState<G>>);
+
+/// Loads the images and configures the optimizer from the command line options,
+/// or from the checkpoint given with `--resume`.
+fn build_optimizer<G: DeserializeOwned>(args: &Args) -> Option<Setup<G>> {
     println!("Loading images...");
-    let images_vec = load_images(&dir, filter, standard_width);
+    let (images_vec, image_infos) = load_images(&args.dir, args.filter.clone(), args.standard_width);
     if images_vec.is_empty() {
         eprintln!("No images loaded.");
+        return None;
+    }
+
+    if let Some(path) = &args.resume {
+        let checkpoint = match read_checkpoint::<G>(Path::new(path)) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        };
+        let mut optimizer = match CollageOptimizer::from_checkpoint(&checkpoint, images_vec) {
+            Ok(optimizer) => optimizer,
+            Err(e) => {
+                eprintln!("{} Use the directory, --filter and --width of the original run.", e);
+                return None;
+            }
+        };
+        if let Some(checkpoint_path) = &args.checkpoint {
+            optimizer = optimizer.checkpoint(checkpoint_path, args.checkpoint_every);
+        }
+        println!("Resuming '{}' at generation {}", path, checkpoint.state.generation);
+        return Some((optimizer, image_infos, Some(checkpoint.state)));
+    }
+
+    let mut weights = match &args.fitness_config {
+        Some(path) => match read_fitness_weights(Path::new(path)) {
+            Ok(weights) => weights,
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        },
+        None => FitnessWeights::default(),
+    };
+    for &(term, weight) in &args.weights {
+        weights.set(term, weight);
+    }
+
+    let mut optimizer = CollageOptimizer::new(images_vec)
+        .population_size(args.population_size)
+        .generations(args.generations)
+        .image_limits(args.min_images, args.max_images)
+        .scale_limits(args.scale_min, args.scale_max)
+        .mutation_rate(args.mutation_rate)
+        .crossover_rate(args.crossover_rate)
+        .crossover(args.crossover)
+        .selection(args.selection)
+        .elitism(args.elitism)
+        .fitness_weights(weights)
+        .aspect_ratio(args.layout.aspect_ratio)
+        .padding(args.layout.padding)
+        .margin(args.layout.margin)
+        .background(args.layout.background)
+        .allow_rotation(args.layout.allow_rotation)
+        .packer(args.layout.packer)
+        .mode(args.layout.mode);
+    if let Some(rgb) = args.target_colour {
+        optimizer = optimizer.target_colour(rgb);
+    }
+    if let Some((width, height)) = args.layout.canvas {
+        optimizer = optimizer.canvas(width, height).fill_canvas(args.layout.fill_canvas);
+    }
+    if let Some(generations) = args.stop.max_stale_generations {
+        optimizer = optimizer.max_stale_generations(generations);
+    }
+    if let Some(fitness) = args.stop.target_fitness {
+        optimizer = optimizer.target_fitness(fitness);
+    }
+    if let Some(limit) = args.stop.time_limit {
+        optimizer = optimizer.time_limit(limit);
+    }
+    if let Some(diversity) = args.stop.min_diversity {
+        optimizer = optimizer.min_diversity(diversity);
+    }
+    if let Some(seed) = args.seed {
+        optimizer = optimizer.seed(seed);
+    }
+    if let Some(path) = &args.checkpoint {
+        optimizer = optimizer.checkpoint(path, args.checkpoint_every);
+    }
+    let mut included = Vec::new();
+    for pattern in &args.include {
+        let ids = resolve_pattern(&image_infos, pattern)?;
+        if ids.is_empty() {
+            eprintln!("--include {} matches no loaded image.", pattern);
+            return None;
+        }
+        println!("Including images {:?} ({})", ids, pattern);
+        optimizer = optimizer.include(&ids);
+        included.extend(ids);
+    }
+    let mut excluded = Vec::new();
+    for pattern in &args.exclude {
+        let ids = resolve_pattern(&image_infos, pattern)?;
+        if ids.is_empty() {
+            eprintln!("Warning: --exclude {} matches no loaded image.", pattern);
+        } else {
+            println!("Excluding images {:?} ({})", ids, pattern);
+        }
+        optimizer = optimizer.exclude(&ids);
+        excluded.extend(ids);
+    }
+    if let Some(threshold) = args.dedupe {
+        match args.dedupe_mode {
+            DedupeMode::Drop => {
+                let mut ids: Vec<u32> = image_infos.keys().copied().filter(|id| !excluded.contains(id)).collect();
+                ids.sort();
+                let dropped = redundant_images(&ids, &included, &image_infos, threshold);
+                println!("Dropping {} near-duplicate images {:?}", dropped.len(), dropped);
+                optimizer = optimizer.exclude(&dropped);
+            }
+            DedupeMode::Penalise => {
+                let pairs = near_duplicate_pairs(&image_infos, threshold);
+                println!("Penalising {} pairs of near-duplicate images {:?}", pairs.len(), pairs);
+                optimizer = optimizer.penalise_near_duplicates(&pairs);
+            }
+        }
+    }
+    Some((optimizer, image_infos, None))
+}
+
+fn resolve_pattern(image_infos: &HashMap<u32, ImageInfo>, pattern: &str) -> Option<Vec<u32>> {
+    match matching_images(image_infos, pattern) {
+        Ok(ids) => Some(ids),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// Prints the collage size and where every image of `indiv` is placed.
+fn print_placements(indiv: &Individual) {
+    let Some((placements, width, height)) = &indiv.packed_layout else {
         return;
+    };
+    println!("Collage dimensions: Width = {}, Height = {}", width, height);
+    for Placement { id, rect, rotated, .. } in placements {
+        println!(
+            "Image ID: {}, Position: ({}, {}), Size: {}x{}{}",
+            id, rect.x, rect.y, rect.width, rect.height, if *rotated { ", rotated" } else { "" }
+        );
+    }
+}
+
+/// Prints the value, weight and weighted value of every fitness term of `indiv`.
+fn print_breakdown(indiv: &Individual, weights: &FitnessWeights) {
+    println!("Fitness {:.5} = rewards / (1 + penalties):", indiv.fitness);
+    println!("  {:<20} {:>7} {:>12} {:>8} {:>12}", "term", "kind", "value", "weight", "weighted");
+    for term in FitnessTerm::ALL {
+        let (value, weight) = (term.value(&indiv.breakdown), weights.get(term));
+        let kind = if term.is_reward() { "reward" } else { "penalty" };
+        println!("  {:<20} {:>7} {:>12.4} {:>8} {:>12.4}", term.name(), kind, value, weight, weight * value);
+    }
+}
+
+/// Writes the layout of `indiv` as JSON, reporting the outcome.
+fn save_layout(
+    indiv: &Individual,
+    optimizer: &CollageOptimizer,
+    image_infos: &HashMap<u32, ImageInfo>,
+    layout_path: &Path,
+    force: bool,
+) {
+    println!("Saving layout as '{}'...", layout_path.display());
+    match build_layout(indiv, optimizer.images(), image_infos, optimizer.layout_config()) {
+        Some(layout) => match write_layout_json(&layout, layout_path, force) {
+            Ok(_) => println!("Layout saved successfully."),
+            Err(e) => eprintln!("{}", e),
+        },
+        None => eprintln!("No layout found for the best solution."),
+    }
+}
+
+fn optimize_single<G: CollageGenome>(args: &Args) {
+    // Fail before the (long) optimization rather than after it
+    let output_path = Path::new(args.output.as_deref().unwrap_or("output.jpg"));
+    if let Err(e) = check_output(output_path, &args.output_options) {
+        eprintln!("{}", e);
+        return;
+    }
+    warn_dropped_alpha(output_path, &args.output_options, args.layout.background);
+    if let Some(layout_path) = &args.layout_json {
+        if Path::new(layout_path).exists() && !args.output_options.force {
+            eprintln!("{} already exists; use --force to overwrite it", layout_path);
+            return;
+        }
     }
 
-    let image_map: HashMap<u32, image::DynamicImage> = images_vec.into_iter().collect();
-    let mut rng = rand::thread_rng();
+    let (optimizer, image_infos, state) = match build_optimizer::<G>(args) {
+        Some(built) => built,
+        None => return,
+    };
 
-    let all_images = image_map.iter().map(|(id,i)|(id.clone(),i.clone())).collect::<Vec<_>>();
-    let mut population: Vec<Individual> = (0..population_size)
-        .map(|_| create_random_individual(&all_images, min_images, max_images, &mut rng))
-        .collect();
+    let start = Instant::now();
+    let run = match state {
+        Some(state) => optimizer.resume(state),
+        None => optimizer.run(),
+    };
+    let result = match run {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    println!("Optimization took {:.2?}", start.elapsed());
+    println!("Stopped after {} generations: {}", result.generations, result.stop_reason);
+    print_placements(&result.best);
+    print_breakdown(&result.best, optimizer.fitness_config());
 
-    // Evaluate initial population in parallel
-    population.par_iter_mut().for_each(|indiv| {
-        evaluate_individual(indiv, &image_map);
-    });
+    println!("Saving image as '{}'...", output_path.display());
+    match save_collage(&result.collage, output_path, &args.output_options) {
+        Ok(_) => println!("Image saved successfully."),
+        Err(e) => eprintln!("{}", e),
+    }
 
-    // GA main loop
-    for gen in 1..=generations {
-        population.sort_by(|a,b| b.fitness.partial_cmp(&a.fitness).unwrap());
-        println!("Generation {}: Best fitness = {:.5}", gen, population[0].fitness);
+    if let Some(layout_path) = &args.layout_json {
+        save_layout(&result.best, &optimizer, &image_infos, Path::new(layout_path), args.output_options.force);
+    }
+}
 
-        let half = population_size/2;
-        let elites = &population[..half];
+/// The `--output` and `--layout-json` directories of a run that writes several
+/// collages, each named by its file stem.
+struct OutputDir<'a> {
+    images: &'a Path,
+    layouts: Option<&'a Path>,
+    format: OutputFormat,
+    options: OutputOptions,
+}
 
-        let mut new_population = Vec::new();
-        // Keep elites
-        new_population.extend_from_slice(elites);
+impl OutputDir<'_> {
+    fn image_path(&self, stem: &str) -> PathBuf {
+        self.images.join(format!("{}.{}", stem, self.format.extension()))
+    }
 
-        // Create new individuals
-        while new_population.len() < population_size {
-            let parent1 = elites.choose(&mut rng).unwrap();
-            let parent2 = elites.choose(&mut rng).unwrap();
+    fn layout_path(&self, stem: &str) -> Option<PathBuf> {
+        self.layouts.map(|dir| dir.join(format!("{}.json", stem)))
+    }
+}
 
-            let mut child = if rng.gen::<f64>() < crossover_rate {
-                crossover(parent1, parent2, &all_images, min_images, max_images, &mut rng)
-            } else {
-                let mut c = parent1.clone();
-                enforce_image_limits(&mut c.image_ids, &all_images, min_images, max_images, &mut rng);
-                c
-            };
+/// Creates the output directories for the collages `stems` and checks that none
+/// of their files exists yet.
+fn prepare_output_dir<'a>(args: &'a Args, stems: &[String]) -> Option<OutputDir<'a>> {
+    let format = args.output_options.format.unwrap_or(OutputFormat::Png);
+    let output_dir = OutputDir {
+        images: Path::new(args.output.as_deref().unwrap_or(".")),
+        layouts: args.layout_json.as_deref().map(Path::new),
+        format,
+        options: OutputOptions {
+            format: Some(format),
+            ..args.output_options.clone()
+        },
+    };
+    for dir in [Some(output_dir.images), output_dir.layouts].into_iter().flatten() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Error creating directory {}: {}", dir.display(), e);
+            return None;
+        }
+    }
 
-            if rng.gen::<f64>() < mutation_rate {
-                mutate(&mut child, &all_images, min_images, max_images, &mut rng);
+    // Fail before the (long) optimization rather than after it
+    for stem in stems {
+        let mut paths = vec![output_dir.image_path(stem)];
+        paths.extend(output_dir.layout_path(stem));
+        if let Some(existing) = paths.iter().find(|p| p.exists()) {
+            if !output_dir.options.force {
+                eprintln!("{} already exists; use --force to overwrite it", existing.display());
+                return None;
             }
+        }
+    }
+    if let Some(stem) = stems.first() {
+        warn_dropped_alpha(&output_dir.image_path(stem), &output_dir.options, args.layout.background);
+    }
+    Some(output_dir)
+}
+
+/// File name of the `page`-th (zero based) page of a partition run, without extension.
+fn page_stem(page: usize) -> String {
+    format!("page_{:03}", page + 1)
+}
+
+fn optimize_pages(args: &Args, pages: usize) {
+    let stems: Vec<String> = (0..pages).map(page_stem).collect();
+    let output_dir = match prepare_output_dir(args, &stems) {
+        Some(output_dir) => output_dir,
+        None => return,
+    };
 
-            new_population.push(child);
+    let (optimizer, image_infos, state) = match build_optimizer::<PartitionIndividual>(args) {
+        Some(built) => built,
+        None => return,
+    };
+
+    let start = Instant::now();
+    let run = match state {
+        Some(state) => optimizer.resume_partition(pages, state),
+        None => optimizer.run_partition(pages),
+    };
+    let result = match run {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
         }
+    };
+    println!("Optimization took {:.2?}", start.elapsed());
+    println!("Stopped after {} generations: {}", result.generations, result.stop_reason);
+
+    for (page, (indiv, collage)) in result.best.pages.iter().zip(&result.collages).enumerate() {
+        let stem = page_stem(page);
+        let path = output_dir.image_path(&stem);
+        println!("Page {}:", page + 1);
+        print_placements(indiv);
+        print_breakdown(indiv, optimizer.fitness_config());
+        println!("Saving page {} ({} images) as '{}'...", page + 1, indiv.image_ids.len(), path.display());
+        match save_collage(collage, &path, &output_dir.options) {
+            Ok(_) => println!("Image saved successfully."),
+            Err(e) => eprintln!("{}", e),
+        }
+        if let Some(layout_path) = output_dir.layout_path(&stem) {
+            save_layout(indiv, &optimizer, &image_infos, &layout_path, output_dir.options.force);
+        }
+    }
+}
+
+fn optimize_pareto<G: CollageGenome>(args: &Args) {
+    let stems: Vec<String> = Tradeoff::ALL.iter().map(|tradeoff| tradeoff.name().to_string()).collect();
+    let output_dir = match prepare_output_dir(args, &stems) {
+        Some(output_dir) => output_dir,
+        None => return,
+    };
+
+    let (optimizer, image_infos, state) = match build_optimizer::<G>(args) {
+        Some(built) => built,
+        None => return,
+    };
 
-        // Evaluate the new population in parallel
-        new_population.par_iter_mut().for_each(|indiv| {
-            evaluate_individual(indiv, &image_map);
-        });
+    let start = Instant::now();
+    let run = match state {
+        Some(state) => optimizer.resume_pareto(state),
+        None => optimizer.run_pareto(),
+    };
+    let result = match run {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    println!("Optimization took {:.2?}", start.elapsed());
+    println!("Stopped after {} generations: {}", result.generations, result.stop_reason);
 
-        population = new_population;
+    println!("{:>4} {:>7} {:>8} {:>12} {:>9}", "#", "images", "free %", "aspect diff", "fitness");
+    for (i, indiv) in result.front.iter().enumerate() {
+        let breakdown = &indiv.breakdown;
+        println!(
+            "{:>4} {:>7} {:>8.2} {:>12.4} {:>9.5}",
+            i + 1,
+            breakdown.image_count,
+            breakdown.free_area_percentage,
+            breakdown.aspect_ratio_diff,
+            indiv.fitness
+        );
     }
 
-    // Final solution
-    population.sort_by(|a,b| b.fitness.partial_cmp(&a.fitness).unwrap());
-    let best = &population[0];
-    println!("Best solution fitness: {:.5}", best.fitness);
+    print_breakdown(&result.front[0], optimizer.fitness_config());
 
-    if let Some((packed_locations, w, h)) = &best.packed_layout {
-        let collage = create_collage(&image_map, packed_locations, *w, *h);
-        println!("Saving image as 'output.jpg'...");
-        match collage.save("output.jpg") {
+    for (tradeoff, i, collage) in &result.picks {
+        let path = output_dir.image_path(tradeoff.name());
+        println!("Saving {} (#{}) as '{}'...", tradeoff.name(), i + 1, path.display());
+        match save_collage(collage, &path, &output_dir.options) {
             Ok(_) => println!("Image saved successfully."),
-            Err(e) => eprintln!("Error saving image: {}", e),
+            Err(e) => eprintln!("{}", e),
+        }
+        if let Some(layout_path) = output_dir.layout_path(tradeoff.name()) {
+            save_layout(&result.front[*i], &optimizer, &image_infos, &layout_path, output_dir.options.force);
         }
-    } else {
-        eprintln!("No layout found for the best solution.");
+    }
+}
+
+fn render(args: RenderArgs) {
+    println!("Rendering layout: {}", args.layout);
+    println!("Scale: {}", args.render_options.scale);
+    println!("Canvas: {:?}", args.render_options.canvas);
+    println!("Padding: {:?}", args.render_options.padding);
+    println!("Margin: {:?}", args.render_options.margin);
+    println!("Background: {:?}", args.render_options.background);
+    println!("Output: {}", args.output);
+
+    let output_path = Path::new(&args.output);
+    if let Err(e) = check_output(output_path, &args.output_options) {
+        eprintln!("{}", e);
+        return;
+    }
+
+    let layout = match read_layout_json(Path::new(&args.layout)) {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    warn_dropped_alpha(output_path, &args.output_options, args.render_options.background.unwrap_or(layout.background));
+
+    let collage = match render_layout(&layout, &args.render_options) {
+        Ok(collage) => collage,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    println!("Collage dimensions: Width = {}, Height = {}", collage.width(), collage.height());
+
+    println!("Saving image as '{}'...", output_path.display());
+    match save_collage(&collage, output_path, &args.output_options) {
+        Ok(_) => println!("Image saved successfully."),
+        Err(e) => eprintln!("{}", e),
     }
 }
@@ -0,0 +1,738 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use image::{DynamicImage, Rgba};
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::{write_checkpoint, Checkpoint};
+use crate::collage::create_collage;
+use crate::evolution::{
+    evaluate_population, evolve_from, initial_state, Elitism, Evolution, EvolutionState, GaParams, Selection,
+    StopCriteria, StopReason,
+};
+use crate::fitness::{FitnessTerm, FitnessWeights};
+use crate::ga::{CollageGenome, CrossoverKind, GaContext, Genome, Individual, PartitionIndividual, SlicingTree};
+use crate::image_handling::{colour_stats, entropy_crop, image_set_hash, srgb_to_lab};
+use crate::packing::{CropStrategy, LayoutConfig, LayoutMode, PackerKind};
+use crate::pareto::{pareto_front, Tradeoff};
+
+/// Outcome of an optimizer run: the fittest individual and its rendered collage.
+pub struct CollageResult {
+    pub best: Individual,
+    pub collage: DynamicImage,
+    /// Seed the run used; passing it to [`CollageOptimizer::seed`] reproduces the result.
+    pub seed: u64,
+    /// Number of generations run.
+    pub generations: usize,
+    pub stop_reason: StopReason,
+}
+
+/// Outcome of a partition run: the best distribution and one collage per page.
+pub struct PartitionResult {
+    pub best: PartitionIndividual,
+    /// Rendered collages, in page order.
+    pub collages: Vec<DynamicImage>,
+    pub seed: u64,
+    pub generations: usize,
+    pub stop_reason: StopReason,
+}
+
+/// Outcome of a Pareto run: the non-dominated collages and the extreme trade-offs among them.
+pub struct ParetoResult {
+    /// The non-dominated collages of the final population, fittest first.
+    pub front: Vec<Individual>,
+    /// Every [`Tradeoff`] with the index of its collage in `front` and the rendered collage.
+    pub picks: Vec<(Tradeoff, usize, DynamicImage)>,
+    pub seed: u64,
+    pub generations: usize,
+    pub stop_reason: StopReason,
+}
+
+/// Builder for a GA run over a set of loaded images.
+///
+/// ```no_run
+/// use image_grid_optimizer::{load_images, CollageOptimizer};
+///
+/// let (images, _infos) = load_images("photos", None, Some(400));
+/// let result = CollageOptimizer::new(images)
+///     .population_size(200)
+///     .generations(500)
+///     .image_limits(10, 20)
+///     .run()
+///     .expect("optimization failed");
+/// result.collage.save("collage.png").unwrap();
+/// ```
+///
+/// Everything but the images is serialized as part of a [`Checkpoint`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CollageOptimizer {
+    #[serde(skip)]
+    images: Arc<HashMap<u32, DynamicImage>>,
+    population_size: usize,
+    generations: usize,
+    min_images: usize,
+    max_images: usize,
+    scale_range: (f64, f64),
+    mutation_rate: f64,
+    crossover_rate: f64,
+    crossover: CrossoverKind,
+    selection: Selection,
+    elitism: Elitism,
+    stop: StopCriteria,
+    fitness: FitnessWeights,
+    target_colour: Option<[u8; 3]>,
+    near_duplicates: Vec<(u32, u32)>,
+    layout: LayoutConfig,
+    include: Vec<u32>,
+    exclude: Vec<u32>,
+    seed: Option<u64>,
+    /// File and interval in generations of the checkpoints.
+    checkpoint: Option<(PathBuf, usize)>,
+    /// Set for a [`run_pareto`](Self::run_pareto), so that its checkpoints resume as one.
+    pareto: bool,
+    verbose: bool,
+}
+
+impl CollageOptimizer {
+    /// Creates an optimizer over `images` using the same defaults as the command line tool.
+    pub fn new(images: Vec<(u32, DynamicImage)>) -> Self {
+        CollageOptimizer {
+            images: Arc::new(images.into_iter().collect()),
+            population_size: 1000,
+            generations: 3000,
+            min_images: 6,
+            max_images: 60,
+            scale_range: (1.0, 1.0),
+            mutation_rate: 0.1,
+            crossover_rate: 0.7,
+            crossover: CrossoverKind::default(),
+            selection: Selection::default(),
+            elitism: Elitism::default(),
+            stop: StopCriteria::default(),
+            fitness: FitnessWeights::default(),
+            target_colour: None,
+            near_duplicates: Vec::new(),
+            layout: LayoutConfig::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            seed: None,
+            checkpoint: None,
+            pareto: false,
+            verbose: true,
+        }
+    }
+
+    pub fn population_size(mut self, population_size: usize) -> Self {
+        self.population_size = population_size;
+        self
+    }
+
+    pub fn generations(mut self, generations: usize) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    /// Sets the minimum and maximum number of images per collage.
+    pub fn image_limits(mut self, min_images: usize, max_images: usize) -> Self {
+        self.min_images = min_images;
+        self.max_images = max_images;
+        self
+    }
+
+    /// Lets the GA resize every image by a factor between `min_scale` and
+    /// `max_scale` of its loaded size (default: 1.0 to 1.0, i.e. no resizing).
+    /// Only used by the packed layout mode and not in partition mode.
+    pub fn scale_limits(mut self, min_scale: f64, max_scale: f64) -> Self {
+        self.scale_range = (min_scale, max_scale);
+        self
+    }
+
+    pub fn mutation_rate(mut self, mutation_rate: f64) -> Self {
+        self.mutation_rate = mutation_rate;
+        self
+    }
+
+    pub fn crossover_rate(mut self, crossover_rate: f64) -> Self {
+        self.crossover_rate = crossover_rate;
+        self
+    }
+
+    /// Selects the recombination operator (default: [`CrossoverKind::Ox`]).
+    pub fn crossover(mut self, crossover: CrossoverKind) -> Self {
+        self.crossover = crossover;
+        self
+    }
+
+    /// Selects how parents are picked (default: [`Selection::Truncation`]).
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Sets how many of the fittest individuals survive unchanged (default: 50%).
+    pub fn elitism(mut self, elitism: Elitism) -> Self {
+        self.elitism = elitism;
+        self
+    }
+
+    /// Stops early after `generations` generations without a new best fitness.
+    pub fn max_stale_generations(mut self, generations: usize) -> Self {
+        self.stop.max_stale_generations = Some(generations);
+        self
+    }
+
+    /// Stops early as soon as the best fitness reaches `fitness`.
+    pub fn target_fitness(mut self, fitness: f64) -> Self {
+        self.stop.target_fitness = Some(fitness);
+        self
+    }
+
+    /// Stops early once the run has taken `limit`; the current generation is finished first.
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.stop.time_limit = Some(limit);
+        self
+    }
+
+    /// Stops early when the population has converged: the mean share of the
+    /// genome in which the individuals differ from the fittest one drops below
+    /// `diversity` (between 0 and 1).
+    pub fn min_diversity(mut self, diversity: f64) -> Self {
+        self.stop.min_diversity = Some(diversity);
+        self
+    }
+
+    /// Sets the weights of the fitness terms (default: [`FitnessWeights::default`]).
+    pub fn fitness_weights(mut self, weights: FitnessWeights) -> Self {
+        self.fitness = weights;
+        self
+    }
+
+    /// Sets the weight of a single fitness term.
+    pub fn weight(mut self, term: FitnessTerm, weight: f64) -> Self {
+        self.fitness.set(term, weight);
+        self
+    }
+
+    /// Pulls the mean colour of the collage toward the RGB colour `rgb`, as strongly
+    /// as the weight of [`FitnessTerm::PaletteTarget`] says.
+    pub fn target_colour(mut self, rgb: [u8; 3]) -> Self {
+        self.target_colour = Some(rgb);
+        self
+    }
+
+    /// Penalises collages that show both images of one of `pairs`, as strongly as
+    /// the weight of [`FitnessTerm::NearDuplicates`] says. See
+    /// [`near_duplicate_pairs`](crate::image_handling::near_duplicate_pairs).
+    pub fn penalise_near_duplicates(mut self, pairs: &[(u32, u32)]) -> Self {
+        self.near_duplicates.extend_from_slice(pairs);
+        self.near_duplicates.sort();
+        self.near_duplicates.dedup();
+        self
+    }
+
+    /// Weights of the fitness terms.
+    pub fn fitness_config(&self) -> &FitnessWeights {
+        &self.fitness
+    }
+
+    /// Sets the target width / height ratio of the collage (default: 1.0).
+    pub fn aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+        self.layout.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    /// Renders the collage at exactly `width` x `height` pixels. The canvas ratio
+    /// replaces the aspect ratio as optimization target.
+    pub fn canvas(mut self, width: u32, height: u32) -> Self {
+        self.layout.canvas = Some((width, height));
+        self
+    }
+
+    /// Packs directly into the canvas set with [`canvas`](Self::canvas) and scales
+    /// the chosen images so that they cover it as completely as possible.
+    pub fn fill_canvas(mut self, fill_canvas: bool) -> Self {
+        self.layout.fill_canvas = fill_canvas;
+        self
+    }
+
+    /// Lets the packer turn images by 90° when that packs them tighter.
+    pub fn allow_rotation(mut self, allow_rotation: bool) -> Self {
+        self.layout.allow_rotation = allow_rotation;
+        self
+    }
+
+    /// Selects how the images are arranged (default: [`LayoutMode::Packed`]).
+    pub fn mode(mut self, mode: LayoutMode) -> Self {
+        self.layout.mode = mode;
+        self
+    }
+
+    /// Selects the packing heuristic (default: [`PackerKind::RectPacker`]).
+    pub fn packer(mut self, packer: PackerKind) -> Self {
+        self.layout.packer = packer;
+        self
+    }
+
+    /// Sets the spacing between neighbouring images in pixels (default: 5).
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.layout.padding = padding;
+        self
+    }
+
+    /// Sets the empty border around the whole collage in pixels (default: 0).
+    pub fn margin(mut self, margin: u32) -> Self {
+        self.layout.margin = margin;
+        self
+    }
+
+    /// Sets the RGBA background colour (default: opaque white).
+    pub fn background(mut self, background: [u8; 4]) -> Self {
+        self.layout.background = background;
+        self
+    }
+
+    /// Layout parameters used for packing, fitness and rendering.
+    pub fn layout_config(&self) -> &LayoutConfig {
+        &self.layout
+    }
+
+    /// Images that must be part of every collage. They count towards the image limits.
+    pub fn include(mut self, ids: &[u32]) -> Self {
+        self.include.extend_from_slice(ids);
+        self.include.sort();
+        self.include.dedup();
+        self
+    }
+
+    /// Images that are never used.
+    pub fn exclude(mut self, ids: &[u32]) -> Self {
+        self.exclude.extend_from_slice(ids);
+        self.exclude.sort();
+        self.exclude.dedup();
+        self
+    }
+
+    /// Seeds the GA so that the same seed and images always produce the same collage.
+    /// Without a seed a random one is drawn and reported in the result.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Saves the state of the run to `path` every `every` generations, so that an
+    /// interrupted run can be continued with [`from_checkpoint`](Self::from_checkpoint).
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>, every: usize) -> Self {
+        self.checkpoint = Some((path.into(), every));
+        self
+    }
+
+    /// Recreates the optimizer of a checkpointed run, with the settings and seed
+    /// of that run. `images` must be the images the run was started with.
+    /// Continue the run by passing the checkpoint state to [`resume`](Self::resume),
+    /// [`resume_partition`](Self::resume_partition) or [`resume_pareto`](Self::resume_pareto).
+    pub fn from_checkpoint<G>(checkpoint: &Checkpoint<G>, images: Vec<(u32, DynamicImage)>) -> Result<Self, String> {
+        let images: HashMap<u32, DynamicImage> = images.into_iter().collect();
+        if image_set_hash(&images) != checkpoint.input_hash {
+            return Err("The images differ from the ones the checkpoint was made with.".to_string());
+        }
+        Ok(CollageOptimizer {
+            images: Arc::new(images),
+            ..checkpoint.optimizer.clone()
+        })
+    }
+
+    /// Enables or disables the per-generation progress output (enabled by default).
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Images the optimizer works on, keyed by image ID.
+    pub fn images(&self) -> &HashMap<u32, DynamicImage> {
+        &self.images
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.images.is_empty() {
+            return Err("No images loaded.".to_string());
+        }
+        if self.population_size == 0 {
+            return Err("Population size must be at least 1.".to_string());
+        }
+        if self.selection == Selection::Tournament(0) {
+            return Err("The tournament size must be at least 1.".to_string());
+        }
+        if self.stop.min_diversity.is_some_and(|d| !(0.0..=1.0).contains(&d)) {
+            return Err("The minimum diversity must be between 0 and 1.".to_string());
+        }
+        if self.checkpoint.as_ref().is_some_and(|(_, every)| *every == 0) {
+            return Err("The checkpoint interval must be at least 1 generation.".to_string());
+        }
+        self.fitness.validate()?;
+        if !self.pareto && self.elitism.count(self.population_size) == self.population_size {
+            return Err("Elitism must leave room for new individuals in the population.".to_string());
+        }
+        let aspect_ratio = self.layout.target_aspect_ratio();
+        if aspect_ratio <= 0.0 || !aspect_ratio.is_finite() {
+            return Err(format!("Invalid aspect ratio: {}", aspect_ratio));
+        }
+        if self.layout.fill_canvas && self.layout.canvas.is_none() {
+            return Err("Filling the canvas requires a canvas size.".to_string());
+        }
+        if self.layout.mode != LayoutMode::Packed && (self.layout.fill_canvas || self.layout.allow_rotation) {
+            return Err("Filling the canvas and rotation are only available in the packed layout mode.".to_string());
+        }
+        let (min_scale, max_scale) = self.scale_range;
+        if !(min_scale > 0.0 && min_scale <= max_scale && max_scale.is_finite()) {
+            return Err(format!(
+                "The scale limits must satisfy 0 < min <= max, got {} and {}",
+                min_scale, max_scale
+            ));
+        }
+        if self.min_images == 0 {
+            return Err("min_images must be at least 1.".to_string());
+        }
+        if self.min_images > self.max_images {
+            return Err(format!(
+                "min_images ({}) must not exceed max_images ({})",
+                self.min_images, self.max_images
+            ));
+        }
+        if let Some(id) = self.include.iter().find(|id| !self.images.contains_key(id)) {
+            return Err(format!("Included image {} is not loaded.", id));
+        }
+        if let Some(id) = self.include.iter().find(|id| self.exclude.contains(id)) {
+            return Err(format!("Image {} is both included and excluded.", id));
+        }
+        let (_, max_images) = self.image_limits_for_mode();
+        if self.include.len() > max_images {
+            return Err(format!(
+                "{} images are included but at most {} can be placed",
+                self.include.len(),
+                max_images
+            ));
+        }
+        if let LayoutMode::Masonry { columns: 0, .. } = self.layout.mode {
+            return Err("The masonry layout needs at least one column.".to_string());
+        }
+        if let Some(count) = self.layout.mode.image_count() {
+            let available = self.images.keys().filter(|id| !self.exclude.contains(id)).count();
+            if count == 0 || count > available {
+                return Err(format!("The grid has {} cells but {} images are available.", count, available));
+            }
+        }
+        if self.images.keys().all(|id| self.exclude.contains(id)) {
+            return Err("All images are excluded.".to_string());
+        }
+        Ok(())
+    }
+
+    /// The image limits, or the exact count a mode such as the grid needs.
+    fn image_limits_for_mode(&self) -> (usize, usize) {
+        match self.layout.mode.image_count() {
+            Some(count) => (count, count),
+            None => (self.min_images, self.max_images),
+        }
+    }
+
+    fn context(&self, pages: usize) -> GaContext<'_> {
+        let (min_images, max_images) = self.image_limits_for_mode();
+        let mut all_ids: Vec<u32> = self.images.keys().copied().filter(|id| !self.exclude.contains(id)).collect();
+        all_ids.sort();
+        let colours = all_ids.par_iter().map(|&id| (id, colour_stats(&self.images[&id]))).collect();
+        GaContext {
+            images: &self.images,
+            all_ids,
+            locked: self.include.clone(),
+            min_images,
+            max_images,
+            crossover: self.crossover,
+            scale_range: self.scale_range,
+            pages,
+            layout: &self.layout,
+            fitness: &self.fitness,
+            colours,
+            target_colour: self.target_colour.map(srgb_to_lab),
+            near_duplicates: self.near_duplicates.clone(),
+        }
+    }
+
+    fn params(&self) -> GaParams {
+        GaParams {
+            population_size: self.population_size,
+            generations: self.generations,
+            mutation_rate: self.mutation_rate,
+            crossover_rate: self.crossover_rate,
+            selection: self.selection,
+            elitism: self.elitism,
+            stop: self.stop.clone(),
+            pareto: self.pareto,
+        }
+    }
+
+    fn resolve_seed(&self) -> u64 {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        if self.verbose {
+            println!("Seed: {}", seed);
+        }
+        seed
+    }
+
+    fn render(&self, indiv: &Individual) -> Result<DynamicImage, String> {
+        let (packed_locations, w, h) = indiv.packed_layout.as_ref().ok_or_else(|| "no layout found".to_string())?;
+        create_collage(&self.images, packed_locations, *w, *h, Rgba(self.layout.background))
+    }
+
+    /// Moves the crops of a grid layout to the most detailed part of each image if
+    /// requested. The position does not change the fitness, so this is only done
+    /// for the final solution.
+    fn place_crops(&self, indiv: &mut Individual) {
+        if let LayoutMode::Grid { crop: CropStrategy::Entropy, .. } = self.layout.mode {
+            if let Some((placements, _, _)) = indiv.packed_layout.as_mut() {
+                for placement in placements {
+                    if let (Some(crop), Some(img)) = (placement.crop, self.images.get(&placement.id)) {
+                        placement.crop = Some(entropy_crop(img, crop));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs the GA from `state`, or from a random population, writing checkpoints if requested.
+    fn evolve<G: Genome + Serialize>(
+        &self,
+        ctx: &GaContext,
+        pages: Option<usize>,
+        seed: u64,
+        state: Option<EvolutionState<G>>,
+    ) -> Evolution<G> {
+        let params = self.params();
+        let state = match state {
+            Some(mut state) => {
+                // Checkpoints hold the genomes only
+                evaluate_population(&mut state.population, ctx);
+                state
+            }
+            None => initial_state(ctx, &params, seed),
+        };
+
+        let input_hash = self.checkpoint.as_ref().map(|_| image_set_hash(&self.images));
+        let settings = CollageOptimizer {
+            seed: Some(seed),
+            ..self.clone()
+        };
+        evolve_from(ctx, &params, state, self.verbose, |state| {
+            if let (Some((path, every)), Some(input_hash)) = (&self.checkpoint, input_hash) {
+                if state.generation % every == 0 {
+                    let checkpoint = Checkpoint {
+                        input_hash,
+                        pages,
+                        optimizer: settings.clone(),
+                        state: state.clone(),
+                    };
+                    if let Err(e) = write_checkpoint(&checkpoint, path) {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Runs the genetic algorithm and renders the best layout found.
+    ///
+    /// The slicing layout evolves [`SlicingTree`]s, the other modes [`Individual`]s;
+    /// either way the best collage is returned as an `Individual`.
+    pub fn run(&self) -> Result<CollageResult, String> {
+        match self.layout.mode {
+            LayoutMode::Slicing => self.run_from::<SlicingTree>(None),
+            _ => self.run_from::<Individual>(None),
+        }
+    }
+
+    /// Continues the run of a checkpoint (see [`from_checkpoint`](Self::from_checkpoint)).
+    /// The genome type must be the one [`run`](Self::run) uses for the layout mode.
+    pub fn resume<G: CollageGenome>(&self, state: EvolutionState<G>) -> Result<CollageResult, String> {
+        self.run_from(Some(state))
+    }
+
+    /// Validates the settings and evolves single collages, returning the result and the seed used.
+    fn evolve_single<G: CollageGenome>(&self, state: Option<EvolutionState<G>>) -> Result<(Evolution<G>, u64), String> {
+        self.validate()?;
+        if !G::supports_mode(self.layout.mode) {
+            return Err(format!("The genome does not support the {:?} layout mode.", self.layout.mode));
+        }
+        let seed = self.resolve_seed();
+        let ctx = self.context(1);
+        Ok((self.evolve(&ctx, None, seed, state), seed))
+    }
+
+    fn run_from<G: CollageGenome>(&self, state: Option<EvolutionState<G>>) -> Result<CollageResult, String> {
+        let (
+            Evolution {
+                mut population,
+                generations,
+                stop_reason,
+            },
+            seed,
+        ) = self.evolve_single(state)?;
+
+        // Final solution
+        let mut best = population.swap_remove(0).into_individual();
+        if self.verbose {
+            println!("Best solution fitness: {:.5}", best.fitness);
+        }
+        self.place_crops(&mut best);
+
+        let collage = self
+            .render(&best)
+            .map_err(|e| format!("Cannot render the best solution: {}", e))?;
+
+        Ok(CollageResult {
+            best,
+            collage,
+            seed,
+            generations,
+            stop_reason,
+        })
+    }
+
+    /// Optimizes the image count, the free area and the aspect ratio deviation as
+    /// separate objectives with NSGA-II instead of weighing them in one fitness
+    /// value, and returns the Pareto front of the final population.
+    ///
+    /// Parents are picked by binary tournaments on their Pareto rank and crowding
+    /// distance and the children compete with the whole population, so the
+    /// selection and elitism settings are not used. The stop criteria still
+    /// refer to the fitness.
+    pub fn run_pareto(&self) -> Result<ParetoResult, String> {
+        match self.layout.mode {
+            LayoutMode::Slicing => self.run_pareto_from::<SlicingTree>(None),
+            _ => self.run_pareto_from::<Individual>(None),
+        }
+    }
+
+    /// Continues the Pareto run of a checkpoint.
+    pub fn resume_pareto<G: CollageGenome>(&self, state: EvolutionState<G>) -> Result<ParetoResult, String> {
+        self.run_pareto_from(Some(state))
+    }
+
+    fn run_pareto_from<G: CollageGenome>(&self, state: Option<EvolutionState<G>>) -> Result<ParetoResult, String> {
+        let optimizer = CollageOptimizer {
+            pareto: true,
+            ..self.clone()
+        };
+        let (
+            Evolution {
+                population,
+                generations,
+                stop_reason,
+            },
+            seed,
+        ) = optimizer.evolve_single(state)?;
+
+        let mut front: Vec<Individual> = pareto_front(population).into_iter().map(G::into_individual).collect();
+        if front.is_empty() {
+            return Err("No layout found for any solution.".to_string());
+        }
+        if self.verbose {
+            println!("Pareto front: {} collages", front.len());
+        }
+        for indiv in &mut front {
+            self.place_crops(indiv);
+        }
+
+        let picks = Tradeoff::ALL
+            .iter()
+            .filter_map(|&tradeoff| tradeoff.pick(&front).map(|i| (tradeoff, i)))
+            .map(|(tradeoff, i)| {
+                let collage = self
+                    .render(&front[i])
+                    .map_err(|e| format!("Cannot render the {} collage: {}", tradeoff.name(), e))?;
+                Ok((tradeoff, i, collage))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(ParetoResult {
+            front,
+            picks,
+            seed,
+            generations,
+            stop_reason,
+        })
+    }
+
+    /// Distributes every image over `pages` collages so that each image appears on
+    /// exactly one page, optimizing the mean quality of the pages.
+    ///
+    /// The image limits are ignored in this mode.
+    pub fn run_partition(&self, pages: usize) -> Result<PartitionResult, String> {
+        self.run_partition_from(pages, None)
+    }
+
+    /// Continues the partition run of a checkpoint over its `pages`.
+    pub fn resume_partition(
+        &self,
+        pages: usize,
+        state: EvolutionState<PartitionIndividual>,
+    ) -> Result<PartitionResult, String> {
+        self.run_partition_from(pages, Some(state))
+    }
+
+    fn run_partition_from(
+        &self,
+        pages: usize,
+        state: Option<EvolutionState<PartitionIndividual>>,
+    ) -> Result<PartitionResult, String> {
+        self.validate()?;
+        if self.layout.mode.image_count().is_some() {
+            return Err("The grid layout cannot be split into pages.".to_string());
+        }
+        if self.layout.mode == LayoutMode::Slicing {
+            return Err("The slicing layout cannot be split into pages.".to_string());
+        }
+        if self.pareto {
+            return Err("The Pareto mode cannot be split into pages.".to_string());
+        }
+        let ctx = self.context(pages);
+        if pages == 0 || pages > ctx.all_ids.len() {
+            return Err(format!(
+                "The number of pages must be between 1 and the number of images ({}), got {}",
+                ctx.all_ids.len(),
+                pages
+            ));
+        }
+        let seed = self.resolve_seed();
+
+        let Evolution {
+            mut population,
+            generations,
+            stop_reason,
+        } = self.evolve(&ctx, Some(pages), seed, state);
+
+        let best = population.swap_remove(0);
+        if self.verbose {
+            println!("Best partition fitness: {:.5}", best.fitness);
+        }
+
+        let collages = best
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| {
+                self.render(page)
+                    .map_err(|e| format!("Cannot render page {} of the best solution: {}", i + 1, e))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(PartitionResult {
+            best,
+            collages,
+            seed,
+            generations,
+            stop_reason,
+        })
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::{File, OpenOptions};
+use std::io::BufWriter;
+use std::path::Path;
+use std::str::FromStr;
+use image::{DynamicImage, ImageOutputFormat};
+
+/// Image formats the collage can be written as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Tiff,
+    Bmp,
+}
+
+impl OutputFormat {
+    /// Infers the format from the extension of `path` (case insensitive).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ext.parse().ok())
+    }
+
+    /// File extension used for generated file names.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Tiff => "tif",
+            OutputFormat::Bmp => "bmp",
+        }
+    }
+
+    /// Whether the format keeps the alpha channel of the collage.
+    pub fn supports_alpha(self) -> bool {
+        matches!(self, OutputFormat::Png | OutputFormat::WebP | OutputFormat::Tiff)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            "webp" => Ok(OutputFormat::WebP),
+            "tif" | "tiff" => Ok(OutputFormat::Tiff),
+            "bmp" => Ok(OutputFormat::Bmp),
+            other => Err(format!("Unsupported output format: {}", other)),
+        }
+    }
+}
+
+/// How the final collage is encoded and written.
+#[derive(Clone, Debug)]
+pub struct OutputOptions {
+    /// Explicit format; inferred from the file extension when `None`.
+    pub format: Option<OutputFormat>,
+    /// JPEG quality from 1 to 100. Ignored by the other formats.
+    pub jpeg_quality: u8,
+    /// Overwrite an existing file instead of refusing to write.
+    pub force: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            format: None,
+            jpeg_quality: 90,
+            force: false,
+        }
+    }
+}
+
+/// Encodes `collage` and writes it to `path`.
+///
+/// Fails if the format cannot be determined or if `path` already exists and
+/// `options.force` is not set.
+pub fn save_collage(collage: &DynamicImage, path: &Path, options: &OutputOptions) -> Result<(), String> {
+    let format = match options.format.or_else(|| OutputFormat::from_path(path)) {
+        Some(format) => format,
+        None => {
+            return Err(format!(
+                "Cannot infer the output format of {}; use a .png, .jpg, .webp, .tif or .bmp extension or pass --format",
+                path.display()
+            ))
+        }
+    };
+
+    let file = create_output_file(path, options.force)?;
+    let mut writer = BufWriter::new(file);
+
+    // JPEG and BMP have no alpha channel, so the collage is flattened to RGB first
+    let result = match format {
+        OutputFormat::Png => collage.write_to(&mut writer, ImageOutputFormat::Png),
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(collage.to_rgb8())
+            .write_to(&mut writer, ImageOutputFormat::Jpeg(options.jpeg_quality.clamp(1, 100))),
+        OutputFormat::WebP => collage.write_to(&mut writer, ImageOutputFormat::WebP),
+        OutputFormat::Tiff => collage.write_to(&mut writer, ImageOutputFormat::Tiff),
+        OutputFormat::Bmp => DynamicImage::ImageRgb8(collage.to_rgb8()).write_to(&mut writer, ImageOutputFormat::Bmp),
+    };
+    result.map_err(|e| format!("Error saving image: {}", e))
+}
+
+/// Creates `path` for writing. Unless `force` is set an existing file is an error,
+/// never silently replaced.
+pub fn create_output_file(path: &Path, force: bool) -> Result<File, String> {
+    // `create_new` makes the existence check and the file creation a single step
+    let mut open_options = OpenOptions::new();
+    open_options.write(true);
+    if force {
+        open_options.create(true).truncate(true);
+    } else {
+        open_options.create_new(true);
+    }
+    open_options.open(path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            format!("{} already exists; use --force to overwrite it", path.display())
+        } else {
+            format!("Error creating {}: {}", path.display(), e)
+        }
+    })
+}
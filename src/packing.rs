@@ -1,69 +1,621 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use image::{DynamicImage, GenericImageView};
-use rect_packer::{Config, Packer, Rect};
+use rect_packer::{Config, Rect};
+use serde::{Deserialize, Serialize};
 
-pub const DESIRED_ASPECT_RATIO: f64 = 1.0;
-const PADDING_SIZE: u32 = 5;
+mod grid;
+mod guillotine;
+mod justified;
+mod masonry;
+mod maxrects;
+mod shelf;
+mod skyline;
+mod slicing;
 
+pub use grid::grid_layout;
+pub use guillotine::GuillotinePacker;
+pub use justified::justify_rows;
+pub use masonry::masonry_layout;
+pub use maxrects::{MaxRectsHeuristic, MaxRectsPacker};
+pub use shelf::ShelfPacker;
+pub use skyline::SkylinePacker;
+pub use slicing::{slicing_layout, slicing_tree, CutDirection, SliceNode};
+
+pub const DEFAULT_ASPECT_RATIO: f64 = 1.0;
+pub const DEFAULT_PADDING: u32 = 5;
+
+/// Runtime parameters shared by packing, fitness evaluation and rendering.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutConfig {
+    /// Target width / height ratio of the collage.
+    pub aspect_ratio: f64,
+    /// Exact output size in pixels. The packed layout is scaled to fit it and
+    /// its ratio replaces `aspect_ratio`.
+    pub canvas: Option<(u32, u32)>,
+    /// Pack directly into the canvas, scaling the images so that they fill it
+    /// as completely as possible. Requires `canvas`.
+    pub fill_canvas: bool,
+    /// Spacing between neighbouring images in pixels.
+    pub padding: u32,
+    /// Empty border around the whole collage in pixels.
+    pub margin: u32,
+    /// Canvas colour as RGBA; an alpha of 0 gives a transparent background.
+    pub background: [u8; 4],
+    /// Let the packer turn images by 90° when that packs them tighter.
+    pub allow_rotation: bool,
+    /// Heuristic that places the images.
+    pub packer: PackerKind,
+    /// How the images are arranged.
+    pub mode: LayoutMode,
+}
+
+impl LayoutConfig {
+    /// The ratio the optimizer aims for: the canvas ratio if a canvas is set.
+    pub fn target_aspect_ratio(&self) -> f64 {
+        match self.canvas {
+            Some((w, h)) => w as f64 / h as f64,
+            None => self.aspect_ratio,
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            aspect_ratio: DEFAULT_ASPECT_RATIO,
+            canvas: None,
+            fill_canvas: false,
+            padding: DEFAULT_PADDING,
+            margin: 0,
+            background: [255, 255, 255, 255],
+            allow_rotation: false,
+            packer: PackerKind::default(),
+            mode: LayoutMode::default(),
+        }
+    }
+}
+
+/// The arrangements the images can be laid out in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutMode {
+    /// Free-form rectangle packing with the configured [`PackerKind`].
+    #[default]
+    Packed,
+    /// Rows of equal width, see [`justify_rows`].
+    JustifiedRows,
+    /// A `rows` x `columns` grid of identical cells, see [`grid_layout`].
+    Grid { rows: u32, columns: u32, crop: CropStrategy },
+    /// Columns of equal width with the images stacked in them, see [`masonry_layout`].
+    Masonry { columns: u32, bottom: BottomEdge },
+    /// The collage cut recursively in two without free area, see [`slicing_layout`].
+    /// The cuts are part of the genome, so it is evolved as a `SlicingTree`.
+    Slicing,
+}
+
+impl LayoutMode {
+    /// Number of images the mode needs, if it is fixed.
+    pub fn image_count(self) -> Option<usize> {
+        match self {
+            LayoutMode::Grid { rows, columns, .. } => Some((rows * columns) as usize),
+            _ => None,
+        }
+    }
+}
+
+/// How the columns of the masonry layout get a common bottom edge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BottomEdge {
+    /// Cut all columns at the height of the shortest one.
+    #[default]
+    Crop,
+    /// Leave the shorter columns open up to the height of the tallest one.
+    Pad,
+}
+
+impl FromStr for BottomEdge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "crop" => Ok(BottomEdge::Crop),
+            "pad" => Ok(BottomEdge::Pad),
+            _ => Err(format!("Unknown bottom edge: {}", s)),
+        }
+    }
+}
+
+/// Which part of an image is kept when it is cropped to its cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CropStrategy {
+    /// The centre of the image.
+    #[default]
+    Center,
+    /// The window with the most detail, measured as luminance entropy.
+    Entropy,
+}
+
+impl FromStr for CropStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "center" | "centre" => Ok(CropStrategy::Center),
+            "entropy" => Ok(CropStrategy::Entropy),
+            _ => Err(format!("Unknown crop strategy: {}", s)),
+        }
+    }
+}
+
+/// A bin packing heuristic that places rectangles one at a time into a fixed bin.
+pub trait Packer {
+    /// Places a `width` x `height` rectangle, or returns `None` if it does not fit.
+    /// With `allow_rotation` the packer may turn it by 90°; the returned rectangle
+    /// then has the swapped size.
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect>;
+}
+
+impl Packer for rect_packer::Packer {
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect> {
+        rect_packer::Packer::pack(self, width, height, allow_rotation)
+    }
+}
+
+/// The packing heuristics available through [`LayoutConfig::packer`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PackerKind {
+    /// Bottom-left skyline packer of the `rect_packer` crate.
+    #[default]
+    RectPacker,
+    /// MaxRects, best short side fit.
+    MaxRectsBssf,
+    /// MaxRects, best area fit.
+    MaxRectsBaf,
+    /// Skyline, minimum waste below each image.
+    Skyline,
+    /// Guillotine cuts, best area fit.
+    Guillotine,
+    /// Rows of shelves.
+    Shelf,
+}
+
+impl PackerKind {
+    pub const ALL: [PackerKind; 6] = [
+        PackerKind::RectPacker,
+        PackerKind::MaxRectsBssf,
+        PackerKind::MaxRectsBaf,
+        PackerKind::Skyline,
+        PackerKind::Guillotine,
+        PackerKind::Shelf,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PackerKind::RectPacker => "rect-packer",
+            PackerKind::MaxRectsBssf => "maxrects-bssf",
+            PackerKind::MaxRectsBaf => "maxrects-baf",
+            PackerKind::Skyline => "skyline",
+            PackerKind::Guillotine => "guillotine",
+            PackerKind::Shelf => "shelf",
+        }
+    }
+
+    /// Creates a packer for a `width` x `height` bin that keeps `margin` pixels
+    /// free along its border and `padding` pixels between rectangles.
+    pub fn create(self, width: i32, height: i32, margin: i32, padding: i32) -> Box<dyn Packer> {
+        // rect_packer handles the spacing itself; the others pack padded items into the inner bin
+        let inner_w = width - 2 * margin + padding;
+        let inner_h = height - 2 * margin + padding;
+        match self {
+            PackerKind::RectPacker => Box::new(rect_packer::Packer::new(Config {
+                width,
+                height,
+                border_padding: margin,
+                rectangle_padding: padding,
+            })),
+            PackerKind::MaxRectsBssf => Spaced::boxed(
+                MaxRectsPacker::new(inner_w, inner_h, MaxRectsHeuristic::BestShortSideFit),
+                margin,
+                padding,
+            ),
+            PackerKind::MaxRectsBaf => Spaced::boxed(
+                MaxRectsPacker::new(inner_w, inner_h, MaxRectsHeuristic::BestAreaFit),
+                margin,
+                padding,
+            ),
+            PackerKind::Skyline => Spaced::boxed(SkylinePacker::new(inner_w, inner_h), margin, padding),
+            PackerKind::Guillotine => Spaced::boxed(GuillotinePacker::new(inner_w, inner_h), margin, padding),
+            PackerKind::Shelf => Spaced::boxed(ShelfPacker::new(inner_w, inner_h), margin, padding),
+        }
+    }
+}
+
+impl fmt::Display for PackerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PackerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PackerKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown packer: {}", s))
+    }
+}
+
+/// Adds margin and padding to a packer without spacing of its own: every item is
+/// packed with `padding` extra pixels on its right and bottom, and the result is
+/// shifted by `margin`.
+struct Spaced<P> {
+    inner: P,
+    margin: i32,
+    padding: i32,
+}
+
+impl<P: Packer + 'static> Spaced<P> {
+    fn boxed(inner: P, margin: i32, padding: i32) -> Box<dyn Packer> {
+        Box::new(Spaced { inner, margin, padding })
+    }
+}
+
+impl<P: Packer> Packer for Spaced<P> {
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect> {
+        let rect = self.inner.pack(width + self.padding, height + self.padding, allow_rotation)?;
+        Some(Rect::new(
+            rect.x + self.margin,
+            rect.y + self.margin,
+            rect.width - self.padding,
+            rect.height - self.padding,
+        ))
+    }
+}
+
+/// The orientations an item may be placed in: as given, and turned if allowed.
+fn orientations(width: i32, height: i32, allow_rotation: bool) -> impl Iterator<Item = (i32, i32)> {
+    let turned = (allow_rotation && width != height).then_some((height, width));
+    std::iter::once((width, height)).chain(turned)
+}
+
+/// Where one image ends up in a packed layout.
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+    pub id: u32,
+    /// Position and drawn size; for a rotated image the size is the rotated one.
+    pub rect: Rect,
+    /// The image is turned 90° clockwise.
+    pub rotated: bool,
+    /// Part of the (turned) image that is drawn; the whole image if `None`.
+    pub crop: Option<Crop>,
+}
+
+/// A region of an image in fractions of its width and height, so that it
+/// applies at any resolution of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Crop {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Crop {
+    /// The same size of crop moved as close as possible to being centred on `(center_x, center_y)`.
+    pub fn centered_at(self, center_x: f64, center_y: f64) -> Crop {
+        Crop {
+            x: (center_x - self.width / 2.0).clamp(0.0, 1.0 - self.width),
+            y: (center_y - self.height / 2.0).clamp(0.0, 1.0 - self.height),
+            ..self
+        }
+    }
+}
+
+/// The centred crop of an `image_w` x `image_h` image that has the aspect ratio of
+/// a `rect_w` x `rect_h` placement, or `None` if less than a pixel would be cut off.
+pub fn cover_crop(image_w: u32, image_h: u32, rect_w: u32, rect_h: u32) -> Option<Crop> {
+    let scale = (rect_w as f64 / image_w as f64).max(rect_h as f64 / image_h as f64);
+    let covered_w = image_w as f64 * scale;
+    let covered_h = image_h as f64 * scale;
+    let excess = (covered_w - rect_w as f64) + (covered_h - rect_h as f64);
+    if excess < 1.0 {
+        return None;
+    }
+    let width = rect_w as f64 / covered_w;
+    let height = rect_h as f64 / covered_h;
+    Some(Crop {
+        x: (1.0 - width) / 2.0,
+        y: (1.0 - height) / 2.0,
+        width,
+        height,
+    })
+}
+
+/// Packed placements of an individual together with the collage width and height.
+pub type PackedLayout = (Vec<Placement>, u32, u32);
+
+/// Packs the images in the given order, each resized by its entry in `scales`
+/// (images without one keep their size). The scales only apply to the packed mode.
 pub fn pack_images(
-    image_ids: &Vec<u32>,
+    image_ids: &[u32],
+    scales: &HashMap<u32, f64>,
     image_map: &HashMap<u32, DynamicImage>,
-) -> (Vec<(u32, Rect)>, u32, u32) {
-    if image_ids.is_empty() {
+    config: &LayoutConfig,
+) -> PackedLayout {
+    let sizes: Vec<(u32, u32, u32)> = image_ids.iter().map(|id| {
+        let (w, h) = image_map.get(id).unwrap().dimensions();
+        let scale = match config.mode {
+            LayoutMode::Packed => scales.get(id).copied().unwrap_or(1.0),
+            _ => 1.0,
+        };
+        let scaled = |v: u32| ((v as f64 * scale).round() as u32).max(1);
+        (*id, scaled(w), scaled(h))
+    }).collect();
+    pack_sizes(&sizes, config)
+}
+
+/// Packs `(id, width, height)` items in the given order; see [`pack_images`].
+pub fn pack_sizes(sizes: &[(u32, u32, u32)], config: &LayoutConfig) -> PackedLayout {
+    if sizes.is_empty() {
         return (vec![], 0, 0);
     }
 
-    let total_area: u64 = image_ids.iter().map(|id| {
-        let img = image_map.get(id).unwrap();
-        let (w, h) = img.dimensions();
-        (w as u64) * (h as u64)
-    }).sum();
+    match config.mode {
+        LayoutMode::Packed => {}
+        LayoutMode::JustifiedRows => return justify_rows(sizes, config),
+        LayoutMode::Grid { rows, columns, .. } => return grid_layout(sizes, rows, columns, config),
+        LayoutMode::Masonry { columns, bottom } => return masonry_layout(sizes, columns, bottom, config),
+        // A sequence of images does not define the cuts; see slicing_layout
+        LayoutMode::Slicing => return (vec![], 0, 0),
+    }
+
+    if let (true, Some((canvas_w, canvas_h))) = (config.fill_canvas, config.canvas) {
+        return pack_to_canvas(sizes, canvas_w, canvas_h, config);
+    }
 
-    let estimated_height = ((total_area as f64 / DESIRED_ASPECT_RATIO).sqrt()) as u32;
-    let estimated_width = (DESIRED_ASPECT_RATIO * estimated_height as f64) as u32;
+    let total_area: u64 = sizes.iter().map(|(_, w, h)| (*w as u64) * (*h as u64)).sum();
+
+    let aspect_ratio = config.target_aspect_ratio();
+    let estimated_height = ((total_area as f64 / aspect_ratio).sqrt()) as u32;
+    let estimated_width = (aspect_ratio * estimated_height as f64) as u32;
+    let margins = 2 * config.margin as i32;
 
     let mut scale_factor = 1.0;
     let max_attempts = 5;
     for _attempt in 0..max_attempts {
-        let pack_w = (estimated_width as f64 * scale_factor) as i32;
-        let pack_h = (estimated_height as f64 * scale_factor) as i32;
-
-        let config = Config {
-            width: pack_w,
-            height: pack_h,
-            border_padding: 0,
-            rectangle_padding: PADDING_SIZE as i32,
-        };
+        let pack_w = (estimated_width as f64 * scale_factor) as i32 + margins;
+        let pack_h = (estimated_height as f64 * scale_factor) as i32 + margins;
 
-        let mut packer = Packer::new(config);
-        let mut packed_locations = Vec::new();
-        let mut max_width = 0;
-        let mut max_height = 0;
-
-        let mut all_fit = true;
-        for id in image_ids {
-            let img = image_map.get(id).unwrap();
-            let (w, h) = img.dimensions();
-            if let Some(rect) = packer.pack(w as i32, h as i32, false) {
-                packed_locations.push((*id, rect));
-                if (rect.x + rect.width) as u32 > max_width {
-                    max_width = (rect.x + rect.width) as u32;
-                }
-                if (rect.y + rect.height) as u32 > max_height {
-                    max_height = (rect.y + rect.height) as u32;
+        if let Some((packed_locations, max_width, max_height)) = try_pack(sizes, 1.0, pack_w, pack_h, config) {
+            // The packer leaves the margin on the top and left; add it on the other sides too
+            return (packed_locations, max_width + config.margin, max_height + config.margin);
+        }
+
+        scale_factor *= 1.2;
+    }
+
+    (vec![], 0, 0)
+}
+
+/// Packs all `(id, width, height)` items, each scaled by `scale`, into a bin of
+/// `pack_w` x `pack_h`. Returns `None` if one of them does not fit; otherwise the
+/// layout size is the extent actually used.
+fn try_pack(
+    sizes: &[(u32, u32, u32)],
+    scale: f64,
+    pack_w: i32,
+    pack_h: i32,
+    config: &LayoutConfig,
+) -> Option<PackedLayout> {
+    let mut packer = config.packer.create(pack_w, pack_h, config.margin as i32, config.padding as i32);
+    let mut packed_locations = Vec::new();
+    let mut max_width = 0;
+    let mut max_height = 0;
+
+    for (id, w, h) in sizes {
+        let w = ((*w as f64 * scale) as i32).max(1);
+        let h = ((*h as f64 * scale) as i32).max(1);
+        let rect = packer.pack(w, h, config.allow_rotation)?;
+        // rect_packer reports a rotation only through the swapped size
+        let rotated = w != h && rect.width != w;
+        packed_locations.push(Placement { id: *id, rect, rotated, crop: None });
+        if (rect.x + rect.width) as u32 > max_width {
+            max_width = (rect.x + rect.width) as u32;
+        }
+        if (rect.y + rect.height) as u32 > max_height {
+            max_height = (rect.y + rect.height) as u32;
+        }
+    }
+
+    Some((packed_locations, max_width, max_height))
+}
+
+/// Packs the items into a fixed `canvas_w` x `canvas_h` frame, scaling all of them
+/// by the largest uniform factor that still lets every item fit.
+///
+/// The returned layout always has the canvas size; it is empty if the items do
+/// not fit even when shrunk to a single pixel.
+fn pack_to_canvas(sizes: &[(u32, u32, u32)], canvas_w: u32, canvas_h: u32, config: &LayoutConfig) -> PackedLayout {
+    let total_area: f64 = sizes.iter().map(|(_, w, h)| *w as f64 * *h as f64).sum();
+    // No scale can cover more than the whole canvas or make one image exceed it
+    let mut hi = (canvas_w as f64 * canvas_h as f64 / total_area).sqrt();
+    for (_, w, h) in sizes {
+        let fit = |w: u32, h: u32| (canvas_w as f64 / w as f64).min(canvas_h as f64 / h as f64);
+        let largest = if config.allow_rotation { fit(*w, *h).max(fit(*h, *w)) } else { fit(*w, *h) };
+        hi = hi.min(largest);
+    }
+
+    let fits = |scale: f64| try_pack(sizes, scale, canvas_w as i32, canvas_h as i32, config);
+    let mut best = match fits(hi) {
+        Some(layout) => return (layout.0, canvas_w, canvas_h),
+        None => None,
+    };
+
+    // Find a fitting lower bound, then narrow the gap by bisection
+    let mut lo = hi;
+    while best.is_none() && lo > 1e-3 {
+        lo /= 2.0;
+        best = fits(lo);
+    }
+    if best.is_none() {
+        return (vec![], 0, 0);
+    }
+    let mut hi_fail = lo * 2.0;
+    for _ in 0..10 {
+        let mid = (lo + hi_fail) / 2.0;
+        match fits(mid) {
+            Some(layout) => {
+                lo = mid;
+                best = Some(layout);
+            }
+            None => hi_fail = mid,
+        }
+    }
+
+    let (packed_locations, _, _) = best.unwrap();
+    (packed_locations, canvas_w, canvas_h)
+}
+
+/// Uniformly scales a packed layout so that it fits into a `canvas_width` x
+/// `canvas_height` canvas. The returned layout has exactly the canvas size; the
+/// renderer centers the scaled images on it.
+///
+/// The `margin` around the layout keeps its size in pixels, everything inside
+/// it (images and the padding between them) is scaled.
+pub fn fit_to_canvas(layout: PackedLayout, canvas_width: u32, canvas_height: u32, margin: u32) -> PackedLayout {
+    let (packed_locations, w, h) = layout;
+    let inner_w = w.saturating_sub(2 * margin);
+    let inner_h = h.saturating_sub(2 * margin);
+    if inner_w == 0 || inner_h == 0 {
+        return (packed_locations, canvas_width, canvas_height);
+    }
+    let scale = (canvas_width.saturating_sub(2 * margin) as f64 / inner_w as f64)
+        .min(canvas_height.saturating_sub(2 * margin) as f64 / inner_h as f64);
+    // Scale edges rather than sizes so that rounding never makes neighbours overlap
+    let m = margin as i32;
+    let scale_coord = |v: i32| ((v - m) as f64 * scale).floor() as i32 + m;
+    let scaled = packed_locations
+        .into_iter()
+        .map(|placement| {
+            let rect = placement.rect;
+            let x = scale_coord(rect.x);
+            let y = scale_coord(rect.y);
+            let width = (scale_coord(rect.x + rect.width) - x).max(1);
+            let height = (scale_coord(rect.y + rect.height) - y).max(1);
+            Placement { rect: Rect { x, y, width, height }, ..placement }
+        })
+        .collect();
+    (scaled, canvas_width, canvas_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: i32 = 300;
+    const HEIGHT: i32 = 200;
+    const MARGIN: i32 = 5;
+    const PADDING: i32 = 4;
+    const SIZES: [(i32, i32); 14] = [
+        (120, 80),
+        (60, 90),
+        (45, 45),
+        (200, 30),
+        (30, 150),
+        (75, 50),
+        (50, 75),
+        (100, 100),
+        (20, 60),
+        (90, 20),
+        (33, 47),
+        (64, 64),
+        (150, 40),
+        (25, 25),
+    ];
+
+    /// Whether the rects are at least `padding` pixels apart.
+    fn apart(a: &Rect, b: &Rect, padding: i32) -> bool {
+        a.x + a.width + padding <= b.x
+            || b.x + b.width + padding <= a.x
+            || a.y + a.height + padding <= b.y
+            || b.y + b.height + padding <= a.y
+    }
+
+    #[test]
+    fn every_packer_keeps_placements_inside_the_bin_and_apart() {
+        for kind in PackerKind::ALL {
+            for allow_rotation in [false, true] {
+                let mut packer = kind.create(WIDTH, HEIGHT, MARGIN, PADDING);
+                let mut placed: Vec<Rect> = Vec::new();
+                for (width, height) in SIZES {
+                    let Some(rect) = packer.pack(width, height, allow_rotation) else {
+                        continue;
+                    };
+                    let turned = allow_rotation && (rect.width, rect.height) == (height, width);
+                    assert!((rect.width, rect.height) == (width, height) || turned, "{}: {:?} for {}x{}", kind, rect, width, height);
+                    assert!(
+                        rect.x >= MARGIN && rect.y >= MARGIN && rect.x + rect.width <= WIDTH - MARGIN && rect.y + rect.height <= HEIGHT - MARGIN,
+                        "{}: {:?} outside of the bin",
+                        kind,
+                        rect
+                    );
+                    for other in &placed {
+                        assert!(apart(&rect, other, PADDING), "{}: {:?} is closer than the padding to {:?}", kind, rect, other);
+                    }
+                    placed.push(rect);
                 }
-            } else {
-                all_fit = false;
-                break;
+                assert!(!placed.is_empty(), "{}: no item placed", kind);
             }
         }
+    }
 
-        if all_fit {
-            return (packed_locations, max_width, max_height);
+    #[test]
+    fn every_packer_rejects_items_larger_than_the_bin() {
+        let (inner_w, inner_h) = (WIDTH - 2 * MARGIN, HEIGHT - 2 * MARGIN);
+        for kind in PackerKind::ALL {
+            for allow_rotation in [false, true] {
+                let mut packer = kind.create(WIDTH, HEIGHT, MARGIN, PADDING);
+                assert!(packer.pack(inner_w + 1, 10, allow_rotation).is_none(), "{}: too wide item placed", kind);
+                assert!(packer.pack(10, inner_h + 1, false).is_none(), "{}: too high item placed", kind);
+                assert!(packer.pack(inner_w + 1, inner_w + 1, allow_rotation).is_none(), "{}: too large item placed", kind);
+                // The space inside the margin is usable exactly
+                assert!(packer.pack(inner_w, inner_h, allow_rotation).is_some(), "{}: item of the inner size rejected", kind);
+            }
         }
+    }
 
-        scale_factor *= 1.2;
+    /// Places every item at the same spot and remembers the sizes it was asked for.
+    struct Fixed {
+        requests: Vec<(i32, i32)>,
     }
 
-    (vec![], 0, 0)
+    impl Packer for Fixed {
+        fn pack(&mut self, width: i32, height: i32, _allow_rotation: bool) -> Option<Rect> {
+            self.requests.push((width, height));
+            Some(Rect::new(10, 20, width, height))
+        }
+    }
+
+    #[test]
+    fn spaced_adds_margin_and_padding() {
+        let mut spaced = Spaced {
+            inner: Fixed { requests: vec![] },
+            margin: MARGIN,
+            padding: PADDING,
+        };
+        assert_eq!(spaced.pack(40, 30, false), Some(Rect::new(10 + MARGIN, 20 + MARGIN, 40, 30)));
+        assert_eq!(spaced.inner.requests, vec![(40 + PADDING, 30 + PADDING)]);
+
+        // Neighbours on a shelf end up exactly the padding apart
+        let mut shelf = PackerKind::Shelf.create(WIDTH, HEIGHT, MARGIN, PADDING);
+        assert_eq!(shelf.pack(10, 10, false), Some(Rect::new(MARGIN, MARGIN, 10, 10)));
+        assert_eq!(shelf.pack(10, 10, false), Some(Rect::new(MARGIN + 10 + PADDING, MARGIN, 10, 10)));
+    }
 }
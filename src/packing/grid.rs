@@ -0,0 +1,42 @@
+use rect_packer::Rect;
+
+use super::{cover_crop, LayoutConfig, PackedLayout, Placement};
+
+/// Lays out `(id, width, height)` items in the given order row by row into a
+/// `rows` x `columns` grid of identical cells. Every image is centre cropped to
+/// the cell aspect ratio; cells beyond the last item stay empty.
+///
+/// The cells take the target aspect ratio of the whole grid and the mean area
+/// of the images.
+pub fn grid_layout(sizes: &[(u32, u32, u32)], rows: u32, columns: u32, config: &LayoutConfig) -> PackedLayout {
+    if sizes.is_empty() || rows == 0 || columns == 0 {
+        return (vec![], 0, 0);
+    }
+
+    let cell_aspect = config.target_aspect_ratio() * rows as f64 / columns as f64;
+    let mean_area = sizes.iter().map(|(_, w, h)| *w as f64 * *h as f64).sum::<f64>() / sizes.len() as f64;
+    let cell_w = ((mean_area * cell_aspect).sqrt().round() as u32).max(1);
+    let cell_h = ((mean_area / cell_aspect).sqrt().round() as u32).max(1);
+
+    let step_x = (cell_w + config.padding) as i32;
+    let step_y = (cell_h + config.padding) as i32;
+    let margin = config.margin as i32;
+    let placements = sizes
+        .iter()
+        .take((rows * columns) as usize)
+        .enumerate()
+        .map(|(i, (id, w, h))| {
+            let (row, column) = (i as i32 / columns as i32, i as i32 % columns as i32);
+            Placement {
+                id: *id,
+                rect: Rect::new(margin + column * step_x, margin + row * step_y, cell_w as i32, cell_h as i32),
+                rotated: false,
+                crop: cover_crop(*w, *h, cell_w, cell_h),
+            }
+        })
+        .collect();
+
+    let width = columns * cell_w + (columns - 1) * config.padding + 2 * config.margin;
+    let height = rows * cell_h + (rows - 1) * config.padding + 2 * config.margin;
+    (placements, width, height)
+}
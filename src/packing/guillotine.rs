@@ -0,0 +1,55 @@
+use rect_packer::Rect;
+
+use super::{orientations, Packer};
+
+/// Guillotine packer: places every item into the free rectangle it fits best
+/// (smallest leftover area) and cuts the rest of that rectangle in two along
+/// the shorter leftover axis.
+pub struct GuillotinePacker {
+    free: Vec<Rect>,
+}
+
+impl GuillotinePacker {
+    pub fn new(width: i32, height: i32) -> Self {
+        let free = if width > 0 && height > 0 { vec![Rect::new(0, 0, width, height)] } else { vec![] };
+        GuillotinePacker { free }
+    }
+}
+
+impl Packer for GuillotinePacker {
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect> {
+        let mut best: Option<(usize, i32, i32, i32)> = None;
+        for (i, free) in self.free.iter().enumerate() {
+            for (w, h) in orientations(width, height, allow_rotation) {
+                if w > free.width || h > free.height {
+                    continue;
+                }
+                let waste = free.area() - w * h;
+                if best.is_none_or(|(_, _, _, best_waste)| waste < best_waste) {
+                    best = Some((i, w, h, waste));
+                }
+            }
+        }
+
+        let (index, w, h, _) = best?;
+        let free = self.free.swap_remove(index);
+        let leftover_w = free.width - w;
+        let leftover_h = free.height - h;
+        let (right, bottom) = if leftover_w < leftover_h {
+            // Horizontal cut: the bottom part spans the full width
+            (
+                Rect::new(free.x + w, free.y, leftover_w, h),
+                Rect::new(free.x, free.y + h, free.width, leftover_h),
+            )
+        } else {
+            // Vertical cut: the right part spans the full height
+            (
+                Rect::new(free.x + w, free.y, leftover_w, free.height),
+                Rect::new(free.x, free.y + h, w, leftover_h),
+            )
+        };
+        self.free.extend([right, bottom].into_iter().filter(|r| r.width > 0 && r.height > 0));
+
+        Some(Rect::new(free.x, free.y, w, h))
+    }
+}
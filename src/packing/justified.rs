@@ -0,0 +1,94 @@
+use rect_packer::Rect;
+
+use super::{cover_crop, LayoutConfig, PackedLayout, Placement};
+
+/// Rows may grow to this multiple of the target height before images are cropped.
+const MAX_ROW_STRETCH: f64 = 1.5;
+
+/// Lays out `(id, width, height)` items in the given order as justified rows:
+/// every row is stretched to the same width while keeping the aspect ratio of
+/// its images, so only row heights differ.
+///
+/// The width follows from the total image area and the target aspect ratio; the
+/// target row height is the mean image height. A row is closed as soon as it is
+/// full, with or without its last image, whichever gives the height closer to
+/// the target. Rows that would grow taller than [`MAX_ROW_STRETCH`] times the
+/// target (typically a short last row) are capped and their images centre cropped.
+pub fn justify_rows(sizes: &[(u32, u32, u32)], config: &LayoutConfig) -> PackedLayout {
+    if sizes.is_empty() {
+        return (vec![], 0, 0);
+    }
+
+    let padding = config.padding as f64;
+    let margin = config.margin as i32;
+    let aspects: Vec<f64> = sizes.iter().map(|(_, w, h)| *w as f64 / *h as f64).collect();
+    let total_area: f64 = sizes.iter().map(|(_, w, h)| *w as f64 * *h as f64).sum();
+    let target_height = sizes.iter().map(|(_, _, h)| *h as f64).sum::<f64>() / sizes.len() as f64;
+    let widest = aspects.iter().cloned().fold(0.0, f64::max) * target_height;
+    let row_width = (total_area * config.target_aspect_ratio()).sqrt().max(widest).round();
+
+    // Height of a row holding `count` images whose aspect ratios sum to `aspect_sum`
+    let row_height = |aspect_sum: f64, count: usize| (row_width - padding * (count - 1) as f64) / aspect_sum;
+
+    let mut rows: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+    let mut aspect_sum = 0.0;
+    for (i, aspect) in aspects.iter().enumerate() {
+        let count = i - start + 1;
+        let full_width = (aspect_sum + aspect) * target_height + padding * (count - 1) as f64;
+        if full_width >= row_width {
+            let with = row_height(aspect_sum + aspect, count);
+            let without = (count > 1).then(|| row_height(aspect_sum, count - 1));
+            match without {
+                Some(without) if (without - target_height).abs() < (with - target_height).abs() => {
+                    rows.push((start, i));
+                    start = i;
+                    aspect_sum = *aspect;
+                }
+                _ => {
+                    rows.push((start, i + 1));
+                    start = i + 1;
+                    aspect_sum = 0.0;
+                }
+            }
+        } else {
+            aspect_sum += aspect;
+        }
+    }
+    if start < aspects.len() {
+        rows.push((start, aspects.len()));
+    }
+
+    let mut placements = Vec::with_capacity(sizes.len());
+    let mut y = margin;
+    for (start, end) in rows {
+        let aspect_sum: f64 = aspects[start..end].iter().sum();
+        let height = row_height(aspect_sum, end - start);
+        let drawn_height = height.min(target_height * MAX_ROW_STRETCH).round().max(1.0) as i32;
+
+        // Round the edges rather than the widths so that every row ends exactly at the same x
+        let mut offset: f64 = 0.0;
+        for i in start..end {
+            let x = margin + offset.round() as i32;
+            offset += aspects[i] * height + padding;
+            let right = if i + 1 == end {
+                margin + row_width as i32
+            } else {
+                margin + (offset - padding).round() as i32
+            };
+            let (id, w, h) = sizes[i];
+            let rect = Rect::new(x, y, (right - x).max(1), drawn_height);
+            placements.push(Placement {
+                id,
+                rect,
+                rotated: false,
+                crop: cover_crop(w, h, rect.width as u32, rect.height as u32),
+            });
+        }
+        y += drawn_height + config.padding as i32;
+    }
+
+    let width = row_width as u32 + 2 * config.margin;
+    let height = (y - config.padding as i32) as u32 + config.margin;
+    (placements, width, height)
+}
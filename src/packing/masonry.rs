@@ -0,0 +1,63 @@
+use rect_packer::Rect;
+
+use super::{cover_crop, BottomEdge, LayoutConfig, PackedLayout, Placement};
+
+/// Lays out `(id, width, height)` items as masonry: `columns` columns of equal
+/// width, every image scaled to the column width and put below the images of
+/// the currently shortest column, in the given order.
+///
+/// The column width is the mean image width; the aspect ratio of the result
+/// only depends on how many images there are per column.
+/// With [`BottomEdge::Crop`] all columns end at the height of the shortest one
+/// and the images crossing that line are cropped; with [`BottomEdge::Pad`] they
+/// end at the tallest one and the shorter columns are left open at the bottom.
+/// The layout is empty if cropping would hide an image completely, e.g. when a
+/// column stays empty.
+pub fn masonry_layout(sizes: &[(u32, u32, u32)], columns: u32, bottom: BottomEdge, config: &LayoutConfig) -> PackedLayout {
+    if sizes.is_empty() || columns == 0 {
+        return (vec![], 0, 0);
+    }
+
+    let padding = config.padding as i32;
+    let margin = config.margin as i32;
+    let mean_width = sizes.iter().map(|(_, w, _)| *w as f64).sum::<f64>() / sizes.len() as f64;
+    let column_width = (mean_width.round() as i32).max(1);
+
+    // Stack every image onto the shortest column
+    let mut column_bottoms = vec![margin; columns as usize];
+    let mut stacked: Vec<(usize, u32, u32, u32, i32, i32)> = Vec::with_capacity(sizes.len());
+    for (id, w, h) in sizes {
+        let column = (0..columns as usize).min_by_key(|&c| column_bottoms[c]).unwrap();
+        let height = ((column_width as f64 * *h as f64 / *w as f64).round() as i32).max(1);
+        stacked.push((column, *id, *w, *h, column_bottoms[column], height));
+        column_bottoms[column] += height + padding;
+    }
+
+    let column_end = match bottom {
+        BottomEdge::Crop => column_bottoms.iter().min(),
+        BottomEdge::Pad => column_bottoms.iter().max(),
+    };
+    let bottom_line = column_end.unwrap() - padding;
+
+    if stacked.iter().any(|&(_, _, _, _, y, _)| y >= bottom_line) {
+        return (vec![], 0, 0);
+    }
+
+    let placements = stacked
+        .into_iter()
+        .map(|(column, id, w, h, y, height)| {
+            let x = margin + column as i32 * (column_width + padding);
+            let drawn_height = height.min(bottom_line - y);
+            Placement {
+                id,
+                rect: Rect::new(x, y, column_width, drawn_height),
+                rotated: false,
+                crop: cover_crop(w, h, column_width as u32, drawn_height as u32),
+            }
+        })
+        .collect();
+
+    let width = (columns as i32 * column_width + (columns as i32 - 1) * padding + 2 * margin) as u32;
+    let height = (bottom_line + margin) as u32;
+    (placements, width, height)
+}
@@ -0,0 +1,101 @@
+use rect_packer::Rect;
+
+use super::{orientations, Packer};
+
+/// How [`MaxRectsPacker`] scores the free rectangles an item fits into.
+#[derive(Clone, Copy, Debug)]
+pub enum MaxRectsHeuristic {
+    /// Smallest leftover along the shorter side.
+    BestShortSideFit,
+    /// Smallest leftover area.
+    BestAreaFit,
+}
+
+/// MaxRects packer: keeps the list of maximal free rectangles of the bin, which
+/// may overlap, and places every item into the best scoring one.
+pub struct MaxRectsPacker {
+    heuristic: MaxRectsHeuristic,
+    free: Vec<Rect>,
+}
+
+impl MaxRectsPacker {
+    pub fn new(width: i32, height: i32, heuristic: MaxRectsHeuristic) -> Self {
+        let free = if width > 0 && height > 0 { vec![Rect::new(0, 0, width, height)] } else { vec![] };
+        MaxRectsPacker { heuristic, free }
+    }
+
+    fn score(&self, free: &Rect, width: i32, height: i32) -> (i32, i32) {
+        let leftover_w = free.width - width;
+        let leftover_h = free.height - height;
+        let short_side = leftover_w.min(leftover_h);
+        match self.heuristic {
+            MaxRectsHeuristic::BestShortSideFit => (short_side, leftover_w.max(leftover_h)),
+            MaxRectsHeuristic::BestAreaFit => (free.area() - width * height, short_side),
+        }
+    }
+
+    /// Replaces every free rectangle overlapping `used` by the parts of it left free.
+    fn split_free(&mut self, used: &Rect) {
+        let mut split = Vec::with_capacity(self.free.len() + 4);
+        for free in &self.free {
+            let overlaps = used.x < free.right()
+                && used.right() > free.x
+                && used.y < free.bottom()
+                && used.bottom() > free.y;
+            if !overlaps {
+                split.push(*free);
+                continue;
+            }
+            if used.x > free.x {
+                split.push(Rect::new(free.x, free.y, used.x - free.x, free.height));
+            }
+            if used.right() < free.right() {
+                split.push(Rect::new(used.right(), free.y, free.right() - used.right(), free.height));
+            }
+            if used.y > free.y {
+                split.push(Rect::new(free.x, free.y, free.width, used.y - free.y));
+            }
+            if used.bottom() < free.bottom() {
+                split.push(Rect::new(free.x, used.bottom(), free.width, free.bottom() - used.bottom()));
+            }
+        }
+        self.free = split;
+    }
+
+    /// Drops free rectangles contained in another one.
+    fn prune_free(&mut self) {
+        let mut i = 0;
+        while i < self.free.len() {
+            let contained = (0..self.free.len()).any(|j| {
+                j != i && self.free[j].contains(&self.free[i]) && (self.free[j] != self.free[i] || j < i)
+            });
+            if contained {
+                self.free.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+impl Packer for MaxRectsPacker {
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect> {
+        let mut best: Option<(Rect, (i32, i32))> = None;
+        for free in &self.free {
+            for (w, h) in orientations(width, height, allow_rotation) {
+                if w > free.width || h > free.height {
+                    continue;
+                }
+                let score = self.score(free, w, h);
+                if best.is_none_or(|(_, best_score)| score < best_score) {
+                    best = Some((Rect::new(free.x, free.y, w, h), score));
+                }
+            }
+        }
+
+        let (rect, _) = best?;
+        self.split_free(&rect);
+        self.prune_free();
+        Some(rect)
+    }
+}
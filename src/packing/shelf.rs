@@ -0,0 +1,59 @@
+use rect_packer::Rect;
+
+use super::{orientations, Packer};
+
+/// A row of the shelf packer: items are placed left to right from `used_width`.
+struct Shelf {
+    y: i32,
+    height: i32,
+    used_width: i32,
+}
+
+/// Shelf packer: fills rows from left to right, putting every item on the
+/// existing shelf that leaves the least height unused and opening a new shelf
+/// at the bottom when none fits.
+pub struct ShelfPacker {
+    width: i32,
+    height: i32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    pub fn new(width: i32, height: i32) -> Self {
+        ShelfPacker {
+            width,
+            height,
+            shelves: vec![],
+        }
+    }
+}
+
+impl Packer for ShelfPacker {
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect> {
+        let mut best: Option<(usize, i32, i32)> = None;
+        for (i, shelf) in self.shelves.iter().enumerate() {
+            for (w, h) in orientations(width, height, allow_rotation) {
+                if h > shelf.height || shelf.used_width + w > self.width {
+                    continue;
+                }
+                if best.is_none_or(|(b, _, best_h)| shelf.height - h < self.shelves[b].height - best_h) {
+                    best = Some((i, w, h));
+                }
+            }
+        }
+        if let Some((i, w, h)) = best {
+            let shelf = &mut self.shelves[i];
+            let rect = Rect::new(shelf.used_width, shelf.y, w, h);
+            shelf.used_width += w;
+            return Some(rect);
+        }
+
+        // Open a new shelf, as low as the item allows
+        let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+        let (w, h) = orientations(width, height, allow_rotation)
+            .filter(|&(w, h)| w <= self.width && y + h <= self.height)
+            .min_by_key(|&(_, h)| h)?;
+        self.shelves.push(Shelf { y, height: h, used_width: w });
+        Some(Rect::new(0, y, w, h))
+    }
+}
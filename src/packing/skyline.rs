@@ -0,0 +1,98 @@
+use rect_packer::Rect;
+
+use super::{orientations, Packer};
+
+/// One horizontal piece of the skyline: the bin is filled below `y` from `x` to `x + width`.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+/// Skyline packer choosing the position that wastes the least area below the
+/// item, breaking ties by the lowest top edge.
+pub struct SkylinePacker {
+    width: i32,
+    height: i32,
+    skyline: Vec<Segment>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32) -> Self {
+        SkylinePacker {
+            width,
+            height,
+            skyline: vec![Segment { x: 0, y: 0, width: width.max(0) }],
+        }
+    }
+
+    /// Height at which an item of `width` resting on segment `start` is placed and
+    /// the area left empty below it, or `None` if it does not fit there.
+    fn fit(&self, start: usize, width: i32, height: i32) -> Option<(i32, i64)> {
+        let x = self.skyline[start].x;
+        if x + width > self.width {
+            return None;
+        }
+        let covered = || {
+            self.skyline[start..]
+                .iter()
+                .take_while(move |s| s.x < x + width)
+                .map(move |s| (s.y, (s.x + s.width).min(x + width) - s.x))
+        };
+        let y = covered().map(|(y, _)| y).max()?;
+        if y + height > self.height {
+            return None;
+        }
+        let waste = covered().map(|(sy, w)| (y - sy) as i64 * w as i64).sum();
+        Some((y, waste))
+    }
+
+    /// Raises the skyline under the newly placed `rect`, which starts where a segment starts.
+    fn add(&mut self, rect: &Rect) {
+        let mut skyline = Vec::with_capacity(self.skyline.len() + 2);
+        for s in &self.skyline {
+            let end = s.x + s.width;
+            if end <= rect.x || s.x >= rect.right() {
+                skyline.push(*s);
+                continue;
+            }
+            if s.x == rect.x {
+                skyline.push(Segment { x: rect.x, y: rect.bottom(), width: rect.width });
+            }
+            if end > rect.right() {
+                skyline.push(Segment { x: rect.right(), y: s.y, width: end - rect.right() });
+            }
+        }
+
+        // Merge neighbours of equal height
+        let mut merged: Vec<Segment> = Vec::with_capacity(skyline.len());
+        for s in skyline {
+            match merged.last_mut() {
+                Some(last) if last.y == s.y => last.width += s.width,
+                _ => merged.push(s),
+            }
+        }
+        self.skyline = merged;
+    }
+}
+
+impl Packer for SkylinePacker {
+    fn pack(&mut self, width: i32, height: i32, allow_rotation: bool) -> Option<Rect> {
+        let mut best: Option<(Rect, (i64, i32))> = None;
+        for start in 0..self.skyline.len() {
+            for (w, h) in orientations(width, height, allow_rotation) {
+                if let Some((y, waste)) = self.fit(start, w, h) {
+                    let score = (waste, y + h);
+                    if best.is_none_or(|(_, best_score)| score < best_score) {
+                        best = Some((Rect::new(self.skyline[start].x, y, w, h), score));
+                    }
+                }
+            }
+        }
+
+        let (rect, _) = best?;
+        self.add(&rect);
+        Some(rect)
+    }
+}
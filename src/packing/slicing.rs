@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use rect_packer::Rect;
+use serde::{Deserialize, Serialize};
+
+use super::{cover_crop, LayoutConfig, PackedLayout, Placement};
+
+/// How a [`SliceNode::Cut`] divides its area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CutDirection {
+    /// The parts are side by side and share their height.
+    Vertical,
+    /// The parts are stacked and share their width.
+    Horizontal,
+}
+
+impl CutDirection {
+    pub fn flipped(self) -> Self {
+        match self {
+            CutDirection::Vertical => CutDirection::Horizontal,
+            CutDirection::Horizontal => CutDirection::Vertical,
+        }
+    }
+}
+
+/// A slicing tree: the collage is cut in two recursively until every part holds one image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SliceNode {
+    Leaf(u32),
+    /// `first` is the left or top part.
+    Cut {
+        direction: CutDirection,
+        first: Box<SliceNode>,
+        second: Box<SliceNode>,
+    },
+}
+
+impl SliceNode {
+    pub fn cut(direction: CutDirection, first: SliceNode, second: SliceNode) -> Self {
+        SliceNode::Cut {
+            direction,
+            first: Box::new(first),
+            second: Box::new(second),
+        }
+    }
+
+    /// The image IDs from left/top to right/bottom, i.e. in pre-order.
+    pub fn leaves(&self) -> Vec<u32> {
+        let mut ids = Vec::new();
+        self.collect_leaves(&mut ids);
+        ids
+    }
+
+    fn collect_leaves(&self, ids: &mut Vec<u32>) {
+        match self {
+            SliceNode::Leaf(id) => ids.push(*id),
+            SliceNode::Cut { first, second, .. } => {
+                first.collect_leaves(ids);
+                second.collect_leaves(ids);
+            }
+        }
+    }
+
+    /// Number of nodes, leaves and cuts.
+    pub fn node_count(&self) -> usize {
+        match self {
+            SliceNode::Leaf(_) => 1,
+            SliceNode::Cut { first, second, .. } => 1 + first.node_count() + second.node_count(),
+        }
+    }
+
+    /// The node with the given pre-order index; the root has index 0.
+    pub fn node(&self, mut index: usize) -> Option<&SliceNode> {
+        self.find(&mut index, &|_| true)
+    }
+
+    /// The node with the given pre-order index, mutably.
+    pub fn node_mut(&mut self, mut index: usize) -> Option<&mut SliceNode> {
+        self.find_mut(&mut index, &|_| true)
+    }
+
+    /// The `index`-th leaf in pre-order, mutably.
+    pub fn leaf_mut(&mut self, mut index: usize) -> Option<&mut SliceNode> {
+        self.find_mut(&mut index, &|node| matches!(node, SliceNode::Leaf(_)))
+    }
+
+    /// The `index`-th cut in pre-order, mutably.
+    pub fn cut_mut(&mut self, mut index: usize) -> Option<&mut SliceNode> {
+        self.find_mut(&mut index, &|node| matches!(node, SliceNode::Cut { .. }))
+    }
+
+    /// The `remaining`-th node in pre-order among those `counts`.
+    fn find(&self, remaining: &mut usize, counts: &dyn Fn(&SliceNode) -> bool) -> Option<&SliceNode> {
+        if counts(self) {
+            if *remaining == 0 {
+                return Some(self);
+            }
+            *remaining -= 1;
+        }
+        match self {
+            SliceNode::Leaf(_) => None,
+            SliceNode::Cut { first, second, .. } => first.find(remaining, counts).or_else(|| second.find(remaining, counts)),
+        }
+    }
+
+    fn find_mut(&mut self, remaining: &mut usize, counts: &dyn Fn(&SliceNode) -> bool) -> Option<&mut SliceNode> {
+        if counts(self) {
+            if *remaining == 0 {
+                return Some(self);
+            }
+            *remaining -= 1;
+        }
+        match self {
+            SliceNode::Leaf(_) => None,
+            SliceNode::Cut { first, second, .. } => match first.find_mut(remaining, counts) {
+                Some(node) => Some(node),
+                None => second.find_mut(remaining, counts),
+            },
+        }
+    }
+
+    /// The tree without the leaves in `ids`; the sibling of a removed leaf takes
+    /// the place of their cut. `None` if no leaf is left.
+    pub fn without(self, ids: &[u32]) -> Option<SliceNode> {
+        match self {
+            SliceNode::Leaf(id) if ids.contains(&id) => None,
+            SliceNode::Leaf(_) => Some(self),
+            SliceNode::Cut { direction, first, second } => match (first.without(ids), second.without(ids)) {
+                (Some(first), Some(second)) => Some(SliceNode::cut(direction, first, second)),
+                (Some(remaining), None) | (None, Some(remaining)) => Some(remaining),
+                (None, None) => None,
+            },
+        }
+    }
+
+    /// The parts a cut divides its area into, with nested cuts in the same
+    /// direction merged: the row or column of parts from left/top to right/bottom.
+    fn parts(&self) -> Vec<&SliceNode> {
+        let mut parts = Vec::new();
+        if let SliceNode::Cut { direction, .. } = self {
+            self.collect_parts(*direction, &mut parts);
+        }
+        parts
+    }
+
+    fn collect_parts<'a>(&'a self, direction: CutDirection, parts: &mut Vec<&'a SliceNode>) {
+        match self {
+            SliceNode::Cut { direction: d, first, second } if *d == direction => {
+                first.collect_parts(direction, parts);
+                second.collect_parts(direction, parts);
+            }
+            _ => parts.push(self),
+        }
+    }
+
+    /// Width / height ratio at which every image of the tree fits its part exactly.
+    /// `None` if an image is missing from `sizes`.
+    fn aspect_ratio(&self, sizes: &HashMap<u32, (u32, u32)>) -> Option<f64> {
+        match self {
+            SliceNode::Leaf(id) => sizes.get(id).map(|&(w, h)| w as f64 / h as f64),
+            SliceNode::Cut { direction, .. } => {
+                let ratios = self.parts().iter().map(|part| part.aspect_ratio(sizes)).collect::<Option<Vec<f64>>>()?;
+                Some(match direction {
+                    // Side by side at a common height the widths add up
+                    CutDirection::Vertical => ratios.iter().sum(),
+                    // Stacked at a common width the heights add up
+                    CutDirection::Horizontal => 1.0 / ratios.iter().map(|a| 1.0 / a).sum::<f64>(),
+                })
+            }
+        }
+    }
+}
+
+/// Lays out the images of a slicing tree edge to edge on a collage of the target
+/// aspect ratio, whose area is the total area of the images in `sizes`.
+///
+/// Every cut divides its part in proportion to the aspect ratios of the two
+/// subtrees, so the images fit without cropping when the aspect ratio of the
+/// whole tree matches the target; otherwise they are centre cropped to their parts.
+/// Nested cuts in the same direction are divided in one go, so the layout does
+/// not depend on how such a row or column is nested and [`slicing_tree`] gets it back.
+pub fn slicing_layout(tree: &SliceNode, sizes: &HashMap<u32, (u32, u32)>, config: &LayoutConfig) -> PackedLayout {
+    if tree.aspect_ratio(sizes).is_none() {
+        return (vec![], 0, 0);
+    }
+    let total_area: f64 = tree
+        .leaves()
+        .iter()
+        .map(|id| sizes[id].0 as f64 * sizes[id].1 as f64)
+        .sum();
+    let aspect_ratio = config.target_aspect_ratio();
+    let width = ((total_area * aspect_ratio).sqrt().round() as i32).max(1);
+    let height = ((width as f64 / aspect_ratio).round() as i32).max(1);
+
+    let margin = config.margin as i32;
+    let mut placements = Vec::new();
+    place(tree, Rect::new(margin, margin, width, height), sizes, config.padding as i32, &mut placements);
+    if placements.iter().any(|p| p.rect.width < 1 || p.rect.height < 1) {
+        return (vec![], 0, 0);
+    }
+    (placements, (width + 2 * margin) as u32, (height + 2 * margin) as u32)
+}
+
+fn place(node: &SliceNode, area: Rect, sizes: &HashMap<u32, (u32, u32)>, padding: i32, placements: &mut Vec<Placement>) {
+    match node {
+        SliceNode::Leaf(id) => {
+            let (w, h) = sizes[id];
+            placements.push(Placement {
+                id: *id,
+                rect: area,
+                rotated: false,
+                crop: cover_crop(w, h, area.width.max(1) as u32, area.height.max(1) as u32),
+            });
+        }
+        SliceNode::Cut { direction, .. } => {
+            let parts = node.parts();
+            // Share of every part in the length that is divided: its width side by
+            // side, its height stacked
+            let shares: Vec<f64> = parts
+                .iter()
+                .map(|part| {
+                    let ratio = part.aspect_ratio(sizes).unwrap();
+                    match direction {
+                        CutDirection::Vertical => ratio,
+                        CutDirection::Horizontal => 1.0 / ratio,
+                    }
+                })
+                .collect();
+            let total: f64 = shares.iter().sum();
+            let length = match direction {
+                CutDirection::Vertical => area.width,
+                CutDirection::Horizontal => area.height,
+            };
+            let available = length - padding * (parts.len() as i32 - 1);
+
+            // Rounding the running sum keeps the rounding errors from adding up
+            let mut start = 0;
+            let mut cumulative = 0.0;
+            for (k, (part, share)) in parts.iter().zip(&shares).enumerate() {
+                cumulative += share;
+                let end = (available as f64 * cumulative / total).round() as i32;
+                let offset = start + k as i32 * padding;
+                let part_area = match direction {
+                    CutDirection::Vertical => Rect::new(area.x + offset, area.y, end - start, area.height),
+                    CutDirection::Horizontal => Rect::new(area.x, area.y + offset, area.width, end - start),
+                };
+                place(part, part_area, sizes, padding, placements);
+                start = end;
+            }
+        }
+    }
+}
+
+/// Recovers a slicing tree from the placements of a [`slicing_layout`], which
+/// are in pre-order. `None` if the placements cannot be cut apart that way.
+pub fn slicing_tree(placements: &[Placement]) -> Option<SliceNode> {
+    if let [placement] = placements {
+        return Some(SliceNode::Leaf(placement.id));
+    }
+    for split in 1..placements.len() {
+        let (first, second) = placements.split_at(split);
+        let direction = if first.iter().map(|p| p.rect.x + p.rect.width).max() <= second.iter().map(|p| p.rect.x).min() {
+            CutDirection::Vertical
+        } else if first.iter().map(|p| p.rect.y + p.rect.height).max() <= second.iter().map(|p| p.rect.y).min() {
+            CutDirection::Horizontal
+        } else {
+            continue;
+        };
+        return Some(SliceNode::cut(direction, slicing_tree(first)?, slicing_tree(second)?));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::ga::random_slicing_tree;
+
+    /// A random tree over `count` images of random sizes.
+    fn random_tree(count: u32, seed: u64) -> (SliceNode, HashMap<u32, (u32, u32)>) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let ids: Vec<u32> = (0..count).collect();
+        let sizes = ids.iter().map(|&id| (id, (rng.gen_range(50..400), rng.gen_range(50..400)))).collect();
+        (random_slicing_tree(&ids, &mut rng).unwrap(), sizes)
+    }
+
+    fn overlap(a: &Rect, b: &Rect) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    /// The images and their rects, which is all a layout consists of.
+    fn rects(layout: &PackedLayout) -> (Vec<(u32, Rect)>, u32, u32) {
+        (layout.0.iter().map(|p| (p.id, p.rect)).collect(), layout.1, layout.2)
+    }
+
+    fn config(padding: u32, margin: u32) -> LayoutConfig {
+        LayoutConfig {
+            aspect_ratio: 1.5,
+            padding,
+            margin,
+            mode: crate::packing::LayoutMode::Slicing,
+            ..LayoutConfig::default()
+        }
+    }
+
+    #[test]
+    fn without_removes_only_the_given_leaves() {
+        let (tree, _) = random_tree(6, 1);
+        let leaves = tree.leaves();
+        let remaining = tree.clone().without(&[leaves[1], leaves[4]]).unwrap();
+        let expected: Vec<u32> = leaves.iter().copied().filter(|&id| id != leaves[1] && id != leaves[4]).collect();
+        assert_eq!(remaining.leaves(), expected);
+        assert_eq!(tree.without(&leaves), None);
+    }
+
+    #[test]
+    fn slicing_layout_fills_the_area_inside_the_margin() {
+        let margin = 7;
+        for seed in 0..30 {
+            let (tree, sizes) = random_tree(1 + seed as u32 % 8, seed);
+            let (placements, width, height) = slicing_layout(&tree, &sizes, &config(0, margin));
+            assert_eq!(placements.len(), sizes.len());
+            let inner = Rect::new(margin as i32, margin as i32, (width - 2 * margin) as i32, (height - 2 * margin) as i32);
+            for p in &placements {
+                assert!(inner.contains(&p.rect), "{:?} outside of {:?}", p.rect, inner);
+            }
+            for (i, a) in placements.iter().enumerate() {
+                for b in &placements[i + 1..] {
+                    assert!(!overlap(&a.rect, &b.rect), "{:?} overlaps {:?}", a.rect, b.rect);
+                }
+            }
+            let covered: i64 = placements.iter().map(|p| p.rect.width as i64 * p.rect.height as i64).sum();
+            assert_eq!(covered, inner.width as i64 * inner.height as i64);
+        }
+    }
+
+    #[test]
+    fn slicing_tree_recovers_the_layout() {
+        for seed in 0..30 {
+            let (tree, sizes) = random_tree(1 + seed as u32 % 8, seed);
+            for padding in [0, 4] {
+                let config = config(padding, 3);
+                let layout = slicing_layout(&tree, &sizes, &config);
+                let recovered = slicing_tree(&layout.0).expect("the placements of a slicing layout can be cut apart");
+                assert_eq!(recovered.leaves(), tree.leaves());
+                assert_eq!(rects(&slicing_layout(&recovered, &sizes, &config)), rects(&layout));
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Pareto ranking for the multi-objective mode, after NSGA-II (Deb et al., 2002):
+//! individuals are ordered by the front they belong to and, within a front, by
+//! how isolated they are from their neighbours.
+
+use crate::ga::{Genome, Individual};
+
+/// Whether `a` is at least as good as `b` in every objective and better in one.
+/// All objectives are maximised.
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    let mut better = false;
+    for (x, y) in a.iter().zip(b) {
+        if x < y {
+            return false;
+        }
+        if x > y {
+            better = true;
+        }
+    }
+    better
+}
+
+/// Splits the points into Pareto fronts, given as indices into `objectives`: the
+/// first front holds the non-dominated points, every following one the points
+/// that are only dominated by points of earlier fronts.
+pub fn non_dominated_fronts(objectives: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let n = objectives.len();
+    let mut dominator_count = vec![0usize; n];
+    let mut dominated: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for i in 0..n {
+        for j in i + 1..n {
+            if dominates(&objectives[i], &objectives[j]) {
+                dominated[i].push(j);
+                dominator_count[j] += 1;
+            } else if dominates(&objectives[j], &objectives[i]) {
+                dominated[j].push(i);
+                dominator_count[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::new();
+    let mut front: Vec<usize> = (0..n).filter(|&i| dominator_count[i] == 0).collect();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &i in &front {
+            for &j in &dominated[i] {
+                dominator_count[j] -= 1;
+                if dominator_count[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        next.sort();
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+/// Crowding distance of every point of `front`: the sum over the objectives of
+/// the gap between its two neighbours, relative to the range of the front. The
+/// extreme points get an infinite distance so that they are always preferred.
+pub fn crowding_distances(objectives: &[Vec<f64>], front: &[usize]) -> Vec<f64> {
+    let mut distances = vec![0.0; front.len()];
+    let Some(&first) = front.first() else {
+        return distances;
+    };
+    let columns = (0..objectives[first].len()).map(|m| front.iter().map(|&i| objectives[i][m]).collect::<Vec<f64>>());
+    for values in columns {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap());
+        let (lowest, highest) = (order[0], order[order.len() - 1]);
+        let range = values[highest] - values[lowest];
+        // Equal values, or infinite ones of points without a layout, tell nothing about the spread
+        if !(range > 0.0 && range.is_finite()) {
+            continue;
+        }
+        distances[lowest] = f64::INFINITY;
+        distances[highest] = f64::INFINITY;
+        for k in 1..order.len() - 1 {
+            distances[order[k]] += (values[order[k + 1]] - values[order[k - 1]]) / range;
+        }
+    }
+    distances
+}
+
+/// Front index (0 for the non-dominated points) and crowding distance of every point.
+pub fn rank_and_crowding(objectives: &[Vec<f64>]) -> Vec<(usize, f64)> {
+    let mut ranking = vec![(0, 0.0); objectives.len()];
+    for (rank, front) in non_dominated_fronts(objectives).iter().enumerate() {
+        for (&i, distance) in front.iter().zip(crowding_distances(objectives, front)) {
+            ranking[i] = (rank, distance);
+        }
+    }
+    ranking
+}
+
+/// The NSGA-II replacement: keeps `n` individuals of `population` front by front;
+/// of the front that no longer fits completely, the least crowded ones are kept.
+pub fn select_survivors<G: Genome>(population: Vec<G>, n: usize) -> Vec<G> {
+    let objectives: Vec<Vec<f64>> = population.iter().map(|g| g.objectives()).collect();
+    let mut kept = Vec::with_capacity(n);
+    for front in non_dominated_fronts(&objectives) {
+        if kept.len() + front.len() <= n {
+            kept.extend(front);
+            continue;
+        }
+        let distances = crowding_distances(&objectives, &front);
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|&a, &b| distances[b].partial_cmp(&distances[a]).unwrap());
+        kept.extend(order.iter().take(n - kept.len()).map(|&k| front[k]));
+        break;
+    }
+
+    let mut slots: Vec<Option<G>> = population.into_iter().map(Some).collect();
+    kept.iter().map(|&i| slots[i].take().unwrap()).collect()
+}
+
+/// The non-dominated individuals of `population` in their original order.
+/// Individuals with an infinite objective (no layout) are left out, as are all
+/// but the first of several with the same objectives.
+pub fn pareto_front<G: Genome>(population: Vec<G>) -> Vec<G> {
+    let population: Vec<G> = population
+        .into_iter()
+        .filter(|g| g.objectives().iter().all(|o| o.is_finite()))
+        .collect();
+    let objectives: Vec<Vec<f64>> = population.iter().map(|g| g.objectives()).collect();
+    let mut front = non_dominated_fronts(&objectives).into_iter().next().unwrap_or_default();
+    front.sort();
+    front.dedup_by(|a, b| objectives[*a] == objectives[*b]);
+
+    let mut slots: Vec<Option<G>> = population.into_iter().map(Some).collect();
+    front.iter().map(|&i| slots[i].take().unwrap()).collect()
+}
+
+/// A corner of the Pareto front of collages that is written as its own collage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tradeoff {
+    MostImages,
+    LeastWhitespace,
+    ClosestAspect,
+    /// The highest fitness, which weighs all objectives against each other.
+    BestFitness,
+}
+
+impl Tradeoff {
+    pub const ALL: [Tradeoff; 4] = [
+        Tradeoff::MostImages,
+        Tradeoff::LeastWhitespace,
+        Tradeoff::ClosestAspect,
+        Tradeoff::BestFitness,
+    ];
+
+    /// Name of the trade-off, used as file name of its collage.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tradeoff::MostImages => "most_images",
+            Tradeoff::LeastWhitespace => "least_whitespace",
+            Tradeoff::ClosestAspect => "closest_aspect",
+            Tradeoff::BestFitness => "best_fitness",
+        }
+    }
+
+    /// Index of the collage of `front` that goes furthest in this direction.
+    /// Ties go to the earlier collage, i.e. the fitter one of a front sorted by fitness.
+    pub fn pick(self, front: &[Individual]) -> Option<usize> {
+        let score = |indiv: &Individual| match self {
+            Tradeoff::MostImages => indiv.breakdown.image_count as f64,
+            Tradeoff::LeastWhitespace => -indiv.breakdown.free_area_percentage,
+            Tradeoff::ClosestAspect => -indiv.breakdown.aspect_ratio_diff,
+            Tradeoff::BestFitness => indiv.fitness,
+        };
+        (0..front.len()).reduce(|best, i| if score(&front[i]) > score(&front[best]) { i } else { best })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::{GaContext, GaRng};
+
+    /// A genome that is nothing but its objectives.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Point(Vec<f64>);
+
+    impl Genome for Point {
+        fn random(_: &GaContext, _: &mut GaRng) -> Self {
+            unreachable!()
+        }
+        fn crossover(&self, _: &Self, _: &GaContext, _: &mut GaRng) -> Self {
+            unreachable!()
+        }
+        fn mutate(&mut self, _: &GaContext, _: &mut GaRng) {}
+        fn repair(&mut self, _: &GaContext, _: &mut GaRng) {}
+        fn evaluate(&mut self, _: &GaContext) {}
+        fn fitness(&self) -> f64 {
+            self.0.iter().sum()
+        }
+        fn distance(&self, _: &Self) -> f64 {
+            0.0
+        }
+        fn objectives(&self) -> Vec<f64> {
+            self.0.clone()
+        }
+    }
+
+    fn points(objectives: &[[f64; 2]]) -> Vec<Point> {
+        objectives.iter().map(|o| Point(o.to_vec())).collect()
+    }
+
+    fn sorted(points: Vec<Point>) -> Vec<Vec<f64>> {
+        let mut objectives: Vec<Vec<f64>> = points.into_iter().map(|p| p.0).collect();
+        objectives.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        objectives
+    }
+
+    #[test]
+    fn dominance_needs_one_better_objective() {
+        assert!(dominates(&[2.0, 1.0], &[1.0, 1.0]));
+        assert!(!dominates(&[1.0, 1.0], &[1.0, 1.0]));
+        assert!(!dominates(&[2.0, 0.0], &[1.0, 1.0]));
+    }
+
+    #[test]
+    fn fronts_of_three_points() {
+        // The first and last trade off against each other and both dominate the middle one
+        let objectives = vec![vec![1.0, 2.0, 3.0], vec![1.0, 1.0, 1.0], vec![3.0, 2.0, 1.0]];
+        assert_eq!(non_dominated_fronts(&objectives), vec![vec![0, 2], vec![1]]);
+
+        let chain = vec![vec![1.0, 1.0], vec![3.0, 3.0], vec![2.0, 2.0]];
+        assert_eq!(non_dominated_fronts(&chain), vec![vec![1], vec![2], vec![0]]);
+    }
+
+    #[test]
+    fn boundary_points_are_infinitely_far_from_the_crowd() {
+        let objectives = vec![vec![0.0, 4.0], vec![1.0, 3.0], vec![2.0, 2.0], vec![4.0, 0.0]];
+        let distances = crowding_distances(&objectives, &[0, 1, 2, 3]);
+        assert_eq!(distances[0], f64::INFINITY);
+        assert_eq!(distances[3], f64::INFINITY);
+        // Gaps between the neighbours relative to the range of 4, summed over both objectives
+        assert_eq!(distances[1], 2.0 / 4.0 + 2.0 / 4.0);
+        assert_eq!(distances[2], 3.0 / 4.0 + 3.0 / 4.0);
+    }
+
+    #[test]
+    fn select_survivors_keeps_whole_fronts_before_cutting_by_crowding() {
+        // Fronts: {(5,0), (3,3), (0,5)}, {(4,0), (2,2), (0,4)} and {(1,1)}
+        let population = points(&[[1.0, 1.0], [2.0, 2.0], [5.0, 0.0], [0.0, 4.0], [3.0, 3.0], [4.0, 0.0], [0.0, 5.0]]);
+
+        let first_two = sorted(select_survivors(population.clone(), 6));
+        assert_eq!(first_two, sorted(points(&[[5.0, 0.0], [3.0, 3.0], [0.0, 5.0], [4.0, 0.0], [2.0, 2.0], [0.0, 4.0]])));
+
+        // Of the second front only its boundary points fit
+        let cut = sorted(select_survivors(population.clone(), 5));
+        assert_eq!(cut, sorted(points(&[[5.0, 0.0], [3.0, 3.0], [0.0, 5.0], [4.0, 0.0], [0.0, 4.0]])));
+
+        assert_eq!(sorted(select_survivors(population.clone(), 7)), sorted(population));
+    }
+}